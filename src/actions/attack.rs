@@ -1,26 +1,54 @@
-use crate::actions::Action;
-use crate::system_sequence::SystemSeq;
+use super::Commandlike;
+use crate::actions::{Action, ActionData, Cost, DamageRange, Step, Targeting};
 use leafwing_terminal::TerminalCommand;
 
+/// Attacks the creature with the `target` name, or the first enemy if no target is given
 #[derive(TerminalCommand)]
 #[terminal_command(name = "attack")]
-pub(super) struct AttackCommand;
+pub(super) struct AttackCommand {
+    /// The name of the creature to attack, ignoring case, spaces and underscores
+    target: Option<String>,
+}
 
-impl Action {
-    /// Creates a new [`Action`] that corresponds to an [`AttackCommand`]
-    pub fn attack() -> Action {
-        Action::new(
-            "Attack",
-            SystemSeq::new()
-                .then(roll_hit)
-                .then(roll_damage)
-                .then(roll_crit),
-        )
+impl Commandlike for AttackCommand {
+    fn target(&self) -> Option<&str> {
+        self.target.as_deref()
     }
 }
 
-fn roll_hit() {}
+/// The bonus added to the attacker's hit roll, in the opposed roll against the defender's dodge roll
+const HIT_BONUS: u8 = 128;
+
+/// The number of action points spent on each attack
+const ATTACK_COST: u8 = 2;
 
-fn roll_damage() {}
+/// The factor that damage is multiplied by on a critical hit
+const CRIT_MULTIPLIER: u8 = 2;
 
-fn roll_crit() {}
+impl Action {
+    /// Creates a new [`Action`] that corresponds to an [`AttackCommand`]
+    ///
+    /// Attacks use four RNG values, in order: hit, dodge, crit and damage.
+    /// If the attack is dodged, the crit and damage values are not used.
+    pub fn attack() -> Action {
+        ActionData {
+            name: "Attack".to_string(),
+            cost: Cost {
+                ap: ATTACK_COST,
+                ..Cost::default()
+            },
+            targeting: Targeting::SingleEnemy,
+            steps: vec![
+                Step::Hit,
+                Step::Dodge {
+                    hit_bonus: HIT_BONUS,
+                },
+                Step::Crit {
+                    multiplier: CRIT_MULTIPLIER,
+                },
+                Step::Damage(DamageRange::Weapon),
+            ],
+        }
+        .to_action()
+    }
+}
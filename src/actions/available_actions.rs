@@ -1,31 +1,52 @@
+use super::{Action, ActionId, Actions};
+use crate::combat_statistics::{ActionPoints, CreatureResources, Life, Mana};
+use crate::creatures::Player;
+use crate::spells::Spellbook;
 use bevy::prelude::*;
 use bevy::utils::HashSet;
+use leafwing_terminal::*;
 
 /// The set of available [`Actions`](crate::actions::Action) available to a creature
 #[derive(Component, Debug, Default)]
 pub struct AvailableActions {
-    set: HashSet<String>,
+    set: HashSet<ActionId>,
 }
 
 impl AvailableActions {
-    /// Inserts a new [`Action`](crate::actions::Action) by its label
+    /// Inserts a new [`Action`](crate::actions::Action) by its [`ActionId`]
     ///
     /// Fails with a warning if [`u8::MAX`](u8) actions are already registered.
-    pub fn insert(&mut self, label: String) {
+    pub fn insert(&mut self, action_id: ActionId) {
         if self.len() < u8::MAX {
-            self.set.insert(label);
+            self.set.insert(action_id);
         } else {
             warn!("Too many actions were inserted!");
         }
     }
 
-    /// Lists the available actions in alphabetical order
-    pub fn list(&self) -> Vec<String> {
-        let mut vec: Vec<String> = self.set.iter().cloned().collect();
-        vec.sort();
+    /// Removes an [`Action`](crate::actions::Action) by its [`ActionId`], returning whether it was present
+    pub fn remove(&mut self, action_id: &ActionId) -> bool {
+        self.set.remove(action_id)
+    }
+
+    /// Is the [`Action`](crate::actions::Action) with this [`ActionId`] available?
+    #[must_use]
+    pub fn contains(&self, action_id: &ActionId) -> bool {
+        self.set.contains(action_id)
+    }
+
+    /// Lists the available actions in alphabetical order of their names
+    pub fn list(&self) -> Vec<ActionId> {
+        let mut vec: Vec<ActionId> = self.set.iter().cloned().collect();
+        vec.sort_by_key(ToString::to_string);
         vec
     }
 
+    /// Removes every action
+    pub fn clear(&mut self) {
+        self.set.clear();
+    }
+
     /// Are there any actions registered
     pub fn is_empty(&self) -> bool {
         self.len() == 0
@@ -36,13 +57,200 @@ impl AvailableActions {
         self.set.len() as u8
     }
 
-    /// Selects a random action from the available set
-    pub fn random(&self, rng: u8) -> String {
+    /// Selects a random action from the available set, or `None` if there are no actions
+    #[must_use]
+    pub fn random(&self, rng: u8) -> Option<ActionId> {
         // Get a sorted list
         let list = self.list();
 
         // Use a modulo function to break this up nicely
-        let index = rng % self.len();
-        list[index as usize].clone()
+        let index = (rng as usize).checked_rem(list.len())?;
+        list.get(index).cloned()
+    }
+}
+
+/// Teaches the `creature` the [`Action`](crate::actions::Action) identified by `action`, adding it to their [`AvailableActions`]
+///
+/// Spells are added to the creature's [`Spellbook`] instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LearnAction {
+    /// The creature learning the action
+    pub creature: Entity,
+    /// The action to learn
+    pub action: ActionId,
+}
+
+/// Makes the `creature` forget the [`Action`](crate::actions::Action) identified by `action`, removing it from their [`AvailableActions`] and [`Spellbook`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForgetAction {
+    /// The creature forgetting the action
+    pub creature: Entity,
+    /// The action to forget
+    pub action: ActionId,
+}
+
+/// Applies [`LearnAction`] and [`ForgetAction`] events to the [`AvailableActions`] and [`Spellbook`] of each creature
+///
+/// Whether each event succeeded is printed to the terminal.
+pub(super) fn learn_and_forget_actions(
+    mut learn_events: EventReader<LearnAction>,
+    mut forget_events: EventReader<ForgetAction>,
+    actions: Res<Actions>,
+    mut query: Query<(
+        Option<&mut AvailableActions>,
+        Option<&mut Spellbook>,
+        Option<&Name>,
+    )>,
+    mut lines: EventWriter<PrintTerminalLine>,
+) {
+    for LearnAction { creature, action } in learn_events.iter() {
+        let (available_actions, spellbook, name) = match query.get_mut(*creature) {
+            Ok(creature) => creature,
+            Err(_) => continue,
+        };
+        let name = name.map_or("Someone", Name::as_str);
+
+        let line = match actions.get(action).map(Action::is_spell) {
+            Ok(true) => match spellbook {
+                Some(mut spellbook) => {
+                    spellbook.insert(action.clone());
+                    format!("{name} learned {action}.")
+                }
+                None => format!("{name} cannot learn {action}, as they have no spellbook."),
+            },
+            Ok(false) => match available_actions {
+                Some(mut available_actions) => {
+                    available_actions.insert(action.clone());
+                    format!("{name} learned {action}.")
+                }
+                None => format!("{name} cannot learn {action}, as they cannot use actions."),
+            },
+            Err(error) => format!("{name} cannot learn {action}: {error}"),
+        };
+        lines.send(PrintTerminalLine::new(line));
+    }
+
+    for ForgetAction { creature, action } in forget_events.iter() {
+        let (available_actions, spellbook, name) = match query.get_mut(*creature) {
+            Ok(creature) => creature,
+            Err(_) => continue,
+        };
+        let name = name.map_or("Someone", Name::as_str);
+
+        let forgot_action = available_actions.map_or(false, |mut available_actions| {
+            available_actions.remove(action)
+        });
+        let forgot_spell = spellbook.map_or(false, |mut spellbook| spellbook.remove(action));
+
+        let line = if forgot_action || forgot_spell {
+            format!("{name} forgot {action}.")
+        } else {
+            format!("{name} does not know {action}.")
+        };
+        lines.send(PrintTerminalLine::new(line));
+    }
+}
+
+/// Lists the actions that the player knows, along with their costs and how many RNG values they use
+#[derive(TerminalCommand)]
+#[terminal_command(name = "actions")]
+pub(super) struct ActionsCommand;
+
+/// Teaches the player an action, even if they could not otherwise learn it
+#[derive(TerminalCommand)]
+#[terminal_command(name = "learn")]
+pub(super) struct LearnCommand {
+    /// The name of the action to learn
+    action: String,
+}
+
+/// Makes the player forget an action
+#[derive(TerminalCommand)]
+#[terminal_command(name = "forget")]
+pub(super) struct ForgetCommand {
+    /// The name of the action to forget
+    action: String,
+}
+
+pub(super) fn actions_command(
+    mut terminal_command: TerminalCommand<ActionsCommand>,
+    actions: Res<Actions>,
+    player_query: Query<
+        (
+            &AvailableActions,
+            Option<&Life>,
+            Option<&Mana>,
+            Option<&ActionPoints>,
+        ),
+        With<Player>,
+    >,
+) {
+    if terminal_command.take().is_none() {
+        return;
+    }
+
+    let (available_actions, life, mana, action_points) = match player_query.get_single() {
+        Ok(player) => player,
+        Err(_) => {
+            terminal_command.reply_failed("There is no player to list the actions of.");
+            return;
+        }
+    };
+    let resources = CreatureResources::new(life, mana, action_points);
+
+    let lines = actions.describe(&resources, |action_id| {
+        available_actions.contains(action_id)
+    });
+    if lines.is_empty() {
+        terminal_command.reply("You do not know any actions.");
+    }
+
+    for line in lines {
+        terminal_command.reply(line);
+    }
+}
+
+pub(super) fn learn_command(
+    mut terminal_command: TerminalCommand<LearnCommand>,
+    actions: Res<Actions>,
+    player_query: Query<Entity, With<Player>>,
+    mut learn_events: EventWriter<LearnAction>,
+) {
+    if let Some(LearnCommand { action }) = terminal_command.take() {
+        match (actions.resolve(&action), player_query.get_single()) {
+            (Some(action_id), Ok(player)) => {
+                // Whether the action was learned is reported once the event is applied
+                learn_events.send(LearnAction {
+                    creature: player,
+                    action: action_id,
+                });
+            }
+            (None, _) => {
+                terminal_command.reply_failed(format!("There is no action named {action}."))
+            }
+            (_, Err(_)) => terminal_command.reply_failed("There is no player to teach."),
+        }
+    }
+}
+
+pub(super) fn forget_command(
+    mut terminal_command: TerminalCommand<ForgetCommand>,
+    actions: Res<Actions>,
+    player_query: Query<Entity, With<Player>>,
+    mut forget_events: EventWriter<ForgetAction>,
+) {
+    if let Some(ForgetCommand { action }) = terminal_command.take() {
+        let action_id = actions
+            .resolve(&action)
+            .unwrap_or_else(|| ActionId::from_name(&action));
+
+        match player_query.get_single() {
+            // Whether the action was forgotten is reported once the event is applied
+            Ok(player) => forget_events.send(ForgetAction {
+                creature: player,
+                action: action_id,
+            }),
+            Err(_) => terminal_command.reply_failed("There is no player to forget actions."),
+        }
     }
 }
@@ -0,0 +1,124 @@
+use crate::combat_flow::{Active, Inactive};
+use crate::creatures::Player;
+use crate::prediction::Outcome;
+use crate::rng::RollKind;
+use bevy::prelude::*;
+
+/// The state shared between the steps of the [`Action`](super::Action) being applied
+///
+/// Owned by the action, and inserted as a resource while each of its steps runs.
+/// It is cleared when the action is reset.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ActionContext {
+    /// The name of the action
+    pub action: String,
+    /// The creature using the action
+    pub actor: Option<Entity>,
+    /// The creatures that the action is used on
+    pub targets: Vec<Entity>,
+    results: Vec<RollResult>,
+    aborted: bool,
+}
+
+/// The result of a single roll made by a step of an action
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RollResult {
+    /// The kind of roll that was made
+    pub roll: RollKind,
+    /// The RNG value that was used
+    pub value: u8,
+    /// What the roll resulted in
+    pub outcome: Outcome,
+}
+
+impl ActionContext {
+    /// Creates a context for the action with the `action_name`, used by the `actor` on the `targets`
+    #[must_use]
+    pub fn new(action_name: String, actor: Entity, targets: Vec<Entity>) -> Self {
+        ActionContext {
+            action: action_name,
+            actor: Some(actor),
+            targets,
+            ..ActionContext::default()
+        }
+    }
+
+    /// Creates a context for the action with the `action_name`,
+    /// used by the first [`Active`] creature on the [`Inactive`] creatures of the opposing side
+    ///
+    /// Used when an action is applied without being queued, and so has no chosen targets.
+    pub fn for_active_creature(world: &mut World, action_name: String) -> Self {
+        let mut actor_query = world.query_filtered::<(Entity, Option<&Player>), With<Active>>();
+        let (actor, actor_is_player) = match actor_query.iter(world).next() {
+            Some((entity, player)) => (entity, player.is_some()),
+            None => {
+                return ActionContext {
+                    action: action_name,
+                    ..ActionContext::default()
+                }
+            }
+        };
+
+        let mut target_query = world.query_filtered::<(Entity, Option<&Player>), With<Inactive>>();
+        let targets = target_query
+            .iter(world)
+            .filter(|(_, player)| player.is_some() != actor_is_player)
+            .map(|(entity, _)| entity)
+            .collect();
+
+        ActionContext::new(action_name, actor, targets)
+    }
+
+    /// The same action, user and targets, with none of the steps applied yet
+    #[must_use]
+    pub fn restarted(&self) -> Self {
+        ActionContext {
+            action: self.action.clone(),
+            actor: self.actor,
+            targets: self.targets.clone(),
+            ..ActionContext::default()
+        }
+    }
+
+    /// The first of the [`targets`](ActionContext::targets), if any
+    #[must_use]
+    pub fn target(&self) -> Option<Entity> {
+        self.targets.first().copied()
+    }
+
+    /// Records the result of a roll, so that later steps can read it
+    pub fn record(&mut self, roll: RollKind, value: u8, outcome: Outcome) {
+        self.results.push(RollResult {
+            roll,
+            value,
+            outcome,
+        });
+    }
+
+    /// The most recent result of the provided kind of `roll`, if one was made
+    #[must_use]
+    pub fn result(&self, roll: RollKind) -> Option<RollResult> {
+        self.results
+            .iter()
+            .rev()
+            .find(|result| result.roll == roll)
+            .copied()
+    }
+
+    /// Every roll made so far, in order
+    #[must_use]
+    pub fn results(&self) -> &[RollResult] {
+        &self.results
+    }
+
+    /// Stops the action early: none of its remaining steps will be applied
+    pub fn abort(&mut self) {
+        self.aborted = true;
+    }
+
+    /// Was the action stopped early?
+    #[must_use]
+    pub fn is_aborted(&self) -> bool {
+        self.aborted
+    }
+}
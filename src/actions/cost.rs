@@ -0,0 +1,209 @@
+use super::ActionError;
+use crate::combat_statistics::{ActionPoints, CreatureResources, Life, Mana};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+/// The resources that a creature spends to use an [`Action`](super::Action)
+///
+/// Costs are checked when the action is queued, and paid when its first step is applied.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Cost {
+    /// The [`ActionPoints`] spent
+    pub ap: u8,
+    /// The [`Mana`] spent
+    pub mana: u8,
+    /// The [`Life`] spent
+    pub life: u8,
+}
+
+/// A kind of resource that can be spent to pay a [`Cost`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(missing_docs)]
+pub enum CostKind {
+    ActionPoints,
+    Mana,
+    Life,
+}
+
+impl Display for CostKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            CostKind::ActionPoints => "AP",
+            CostKind::Mana => "mana",
+            CostKind::Life => "life",
+        })
+    }
+}
+
+impl Cost {
+    /// Checks that a creature with the provided `resources` can pay this cost
+    ///
+    /// Creatures cannot pay life costs that would kill them.
+    /// Resources that the creature does not have count as zero.
+    pub fn check(&self, resources: &CreatureResources) -> Result<(), ActionError> {
+        let requirements = [
+            (CostKind::ActionPoints, self.ap, resources.action_points),
+            (CostKind::Mana, self.mana, resources.mana),
+            // Paying life must leave the creature alive
+            (
+                CostKind::Life,
+                if self.life > 0 {
+                    self.life.saturating_add(1)
+                } else {
+                    0
+                },
+                resources.life,
+            ),
+        ];
+
+        for (resource, required, available) in requirements {
+            let available = available.unwrap_or_default();
+            if available < required {
+                return Err(ActionError::CannotAfford {
+                    resource,
+                    required,
+                    available,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Spends the resources of the `entity` to pay this cost
+    ///
+    /// Either the whole cost is paid, or none of it is.
+    pub fn pay(&self, world: &mut World, entity: Entity) -> Result<(), ActionError> {
+        let resources = CreatureResources::new(
+            world.get::<Life>(entity),
+            world.get::<Mana>(entity),
+            world.get::<ActionPoints>(entity),
+        );
+        self.check(&resources)?;
+
+        if let Some(mut action_points) = world.get_mut::<ActionPoints>(entity) {
+            *action_points -= self.ap;
+        }
+
+        if let Some(mut mana) = world.get_mut::<Mana>(entity) {
+            *mana -= self.mana;
+        }
+
+        if let Some(mut life) = world.get_mut::<Life>(entity) {
+            *life -= self.life;
+        }
+
+        Ok(())
+    }
+
+    /// Describes the cost, such as "2 AP, 5 mana"
+    #[must_use]
+    pub fn describe(&self) -> String {
+        let parts: Vec<String> = [
+            (self.ap, CostKind::ActionPoints),
+            (self.mana, CostKind::Mana),
+            (self.life, CostKind::Life),
+        ]
+        .iter()
+        .filter(|(amount, _)| *amount > 0)
+        .map(|(amount, resource)| format!("{amount} {resource}"))
+        .collect();
+
+        if parts.is_empty() {
+            "free".to_string()
+        } else {
+            parts.join(", ")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combat_statistics::Resource;
+
+    const COST: Cost = Cost {
+        ap: 2,
+        mana: 5,
+        life: 3,
+    };
+
+    fn resources(life: u8, mana: u8, action_points: u8) -> CreatureResources {
+        CreatureResources {
+            life: Some(life),
+            mana: Some(mana),
+            action_points: Some(action_points),
+        }
+    }
+
+    fn creature(world: &mut World, life: u8, mana: u8, action_points: u8) -> Entity {
+        world
+            .spawn()
+            .insert_bundle((
+                Life::new(life),
+                Mana::new(mana),
+                ActionPoints::new(action_points),
+            ))
+            .id()
+    }
+
+    #[test]
+    fn costs_are_refused_when_a_resource_is_short() {
+        assert_eq!(
+            COST.check(&resources(10, 4, 2)),
+            Err(ActionError::CannotAfford {
+                resource: CostKind::Mana,
+                required: 5,
+                available: 4,
+            })
+        );
+    }
+
+    #[test]
+    fn life_costs_must_leave_the_creature_alive() {
+        assert_eq!(
+            COST.check(&resources(3, 5, 2)),
+            Err(ActionError::CannotAfford {
+                resource: CostKind::Life,
+                required: 4,
+                available: 3,
+            })
+        );
+        assert_eq!(COST.check(&resources(4, 5, 2)), Ok(()));
+    }
+
+    #[test]
+    fn missing_resources_count_as_zero() {
+        let resources = CreatureResources {
+            mana: None,
+            ..resources(10, 0, 2)
+        };
+
+        assert!(COST.check(&resources).is_err());
+        assert_eq!(Cost { mana: 0, ..COST }.check(&resources), Ok(()));
+    }
+
+    #[test]
+    fn paying_spends_every_resource() {
+        let mut world = World::new();
+        let entity = creature(&mut world, 10, 5, 3);
+
+        assert_eq!(COST.pay(&mut world, entity), Ok(()));
+        assert_eq!(world.get::<Life>(entity).unwrap().current(), 7);
+        assert_eq!(world.get::<Mana>(entity).unwrap().current(), 0);
+        assert_eq!(world.get::<ActionPoints>(entity).unwrap().current(), 1);
+    }
+
+    #[test]
+    fn refused_costs_spend_nothing() {
+        let mut world = World::new();
+        let entity = creature(&mut world, 10, 4, 3);
+
+        assert!(COST.pay(&mut world, entity).is_err());
+        assert_eq!(world.get::<Life>(entity).unwrap().current(), 10);
+        assert_eq!(world.get::<Mana>(entity).unwrap().current(), 4);
+        assert_eq!(world.get::<ActionPoints>(entity).unwrap().current(), 3);
+    }
+}
@@ -0,0 +1,387 @@
+use super::steps::{build_steps, max_draws, predict_steps, DamageRange, Step};
+use super::{Action, ActionId, Actions, Cost, Targeting};
+use crate::system_sequence::SeqError;
+use bevy::asset::{AssetLoader, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::utils::BoxedFuture;
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+/// Loads every action in the `assets/actions` folder, and reloads them when they change
+///
+/// Action files use the `.action.ron` extension, and contain a single [`ActionData`].
+/// Requires the [`ActionPlugin`](super::ActionPlugin) and Bevy's `AssetPlugin`.
+pub struct ActionAssetPlugin;
+
+impl Plugin for ActionAssetPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<ActionData>()
+            .init_asset_loader::<ActionLoader>()
+            .init_resource::<ActionHandles>()
+            .add_system(sync_action_assets);
+    }
+}
+
+/// A declarative description of an [`Action`], which can be stored in an asset file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TypeUuid)]
+#[uuid = "2cf87d60-d402-4f16-abfc-27c0e3587194"]
+pub struct ActionData {
+    /// The name of the action
+    pub name: String,
+    /// The resources spent to use the action
+    #[serde(default)]
+    pub cost: Cost,
+    /// Which creatures the action can be used on
+    #[serde(default)]
+    pub targeting: Targeting,
+    /// The steps of the action, applied in order
+    pub steps: Vec<Step>,
+}
+
+impl ActionData {
+    /// The most times that a [`Step::Repeat`] can apply its steps
+    pub const MAX_REPEATS: usize = 256;
+
+    /// The most steps that an action can apply, counting each repetition of a [`Step::Repeat`] separately
+    pub const MAX_STEPS: usize = 1024;
+
+    /// Parses an [`ActionData`] from a string in the RON format, checking that it is valid
+    pub fn from_ron(ron: &str) -> Result<Self, ActionDataError> {
+        let data: ActionData = ron::de::from_str(ron).map_err(ActionDataError::Parse)?;
+        data.validate()?;
+        Ok(data)
+    }
+
+    /// Checks that the name does not belong to a built-in action, that every range in the steps has a minimum no greater than its maximum,
+    /// that every polymorph has a form to choose, that no repeat exceeds [`ActionData::MAX_REPEATS`],
+    /// that nested repeats apply no more than [`ActionData::MAX_STEPS`] steps,
+    /// and that every check jumps to a unique label later in the same list of steps
+    pub fn validate(&self) -> Result<(), ActionDataError> {
+        /// The number of steps that are applied once every repeat is expanded, not counting labels
+        fn expanded_len(steps: &[Step]) -> usize {
+            steps
+                .iter()
+                .map(|step| match step {
+                    Step::Repeat { times, steps } => times.saturating_mul(expanded_len(steps)),
+                    Step::Label(_) => 0,
+                    _ => 1,
+                })
+                .fold(0, usize::saturating_add)
+        }
+
+        fn validate_steps(steps: &[Step]) -> Result<(), ActionDataError> {
+            steps
+                .iter()
+                .enumerate()
+                .try_for_each(|(index, step)| match step {
+                    Step::Damage(DamageRange::Fixed { min, max })
+                    | Step::Heal { min, max }
+                    | Step::DrainMana { min, max }
+                        if min > max =>
+                    {
+                        Err(ActionDataError::InvalidRange {
+                            min: *min,
+                            max: *max,
+                        })
+                    }
+                    Step::Polymorph { forms } if forms.is_empty() => Err(ActionDataError::NoForms),
+                    Step::Repeat { times, .. } if *times > ActionData::MAX_REPEATS => {
+                        Err(ActionDataError::TooManyRepeats { times: *times })
+                    }
+                    Step::Repeat { steps, .. } => validate_steps(steps),
+                    // Jumps only go forwards, so that actions always finish and `max_draws` stays an upper bound
+                    Step::Check {
+                        otherwise: Some(label),
+                        ..
+                    } if !steps[index + 1..].contains(&Step::Label(label.clone())) => {
+                        Err(ActionDataError::UnknownLabel(label.clone()))
+                    }
+                    _ => Ok(()),
+                })
+        }
+
+        // Otherwise the data would silently replace the built-in action with the same id
+        if !matches!(self.id(), ActionId::Data(_)) {
+            return Err(ActionDataError::ReservedName(self.name.clone()));
+        }
+
+        validate_steps(&self.steps)?;
+
+        // Each repeat is checked on its own, but nested repeats multiply
+        let steps = expanded_len(&self.steps);
+        if steps > ActionData::MAX_STEPS {
+            return Err(ActionDataError::TooManySteps { steps });
+        }
+
+        // Labels inside repeats are duplicated once for each repetition
+        match build_steps(&self.steps).build() {
+            Ok(_) => Ok(()),
+            Err(SeqError::DuplicateLabel(label)) => Err(ActionDataError::DuplicateLabel(label)),
+            Err(SeqError::UnknownLabel(label)) => Err(ActionDataError::UnknownLabel(label)),
+        }
+    }
+
+    /// The [`ActionId`] that identifies the action described by this data
+    #[must_use]
+    pub fn id(&self) -> ActionId {
+        ActionId::from_name(&self.name)
+    }
+
+    /// Is the action a spell, which has a [`Step::Cast`] that can fizzle?
+    #[must_use]
+    pub fn is_spell(&self) -> bool {
+        self.steps
+            .iter()
+            .any(|step| matches!(step, Step::Cast { .. }))
+    }
+
+    /// Compiles this data into an [`Action`], which predicts its own outcome
+    #[must_use]
+    pub fn to_action(&self) -> Action {
+        let steps = self.steps.clone();
+        let predicted_steps = self.steps.clone();
+
+        let action = Action::new(self.id(), move || build_steps(&steps))
+            .with_cost(self.cost)
+            .with_targeting(self.targeting)
+            .with_max_draws(max_draws(&self.steps))
+            .with_predictor(move |context, values| {
+                predict_steps(&predicted_steps, context, values)
+            });
+
+        if self.is_spell() {
+            action.as_spell()
+        } else {
+            action
+        }
+    }
+}
+
+/// The reasons that an [`ActionData`] could not be loaded
+#[derive(Debug)]
+pub enum ActionDataError {
+    /// The data is not valid RON, or does not describe an action
+    Parse(ron::Error),
+    /// The name identifies a built-in action, such as "Attack"
+    ReservedName(String),
+    /// A range of values has a minimum greater than its maximum
+    InvalidRange {
+        /// The minimum of the range
+        min: u8,
+        /// The maximum of the range
+        max: u8,
+    },
+    /// A polymorph has no forms to choose from
+    NoForms,
+    /// A repeat applies its steps more than [`ActionData::MAX_REPEATS`] times
+    TooManyRepeats {
+        /// The number of times that the steps would be applied
+        times: usize,
+    },
+    /// Repeats within repeats apply more than [`ActionData::MAX_STEPS`] steps in total
+    TooManySteps {
+        /// The number of steps that would be applied
+        steps: usize,
+    },
+    /// A check jumps to a label that does not come after it in the same list of steps
+    UnknownLabel(String),
+    /// The same label marks more than one step
+    DuplicateLabel(String),
+}
+
+impl Display for ActionDataError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ActionDataError::Parse(error) => {
+                f.write_fmt(format_args!("Could not parse action: {error}"))
+            }
+            ActionDataError::ReservedName(name) => f.write_fmt(format_args!(
+                "The name {name} is reserved for a built-in action."
+            )),
+            ActionDataError::InvalidRange { min, max } => f.write_fmt(format_args!(
+                "The range {min}-{max} has a minimum greater than its maximum."
+            )),
+            ActionDataError::NoForms => f.write_str("A polymorph must have at least one form."),
+            ActionDataError::TooManyRepeats { times } => f.write_fmt(format_args!(
+                "Steps can be repeated at most {} times, not {times}.",
+                ActionData::MAX_REPEATS
+            )),
+            ActionDataError::TooManySteps { steps } => f.write_fmt(format_args!(
+                "Actions can apply at most {} steps, not {steps}.",
+                ActionData::MAX_STEPS
+            )),
+            ActionDataError::UnknownLabel(label) => {
+                f.write_fmt(format_args!("No later step is labelled {label}."))
+            }
+            ActionDataError::DuplicateLabel(label) => {
+                f.write_fmt(format_args!("The label {label} is used more than once."))
+            }
+        }
+    }
+}
+
+impl std::error::Error for ActionDataError {}
+
+/// Loads `.action.ron` files as [`ActionData`] assets
+#[derive(Default)]
+struct ActionLoader;
+
+impl AssetLoader for ActionLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let data = ActionData::from_ron(std::str::from_utf8(bytes)?)?;
+            load_context.set_default_asset(LoadedAsset::new(data));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["action.ron"]
+    }
+}
+
+/// Keeps the handles of the loaded action assets alive, so that they are not unloaded
+struct ActionHandles {
+    _handles: Vec<HandleUntyped>,
+}
+
+impl FromWorld for ActionHandles {
+    fn from_world(world: &mut World) -> Self {
+        let asset_server = world.get_resource::<AssetServer>().unwrap();
+
+        match asset_server.load_folder("actions") {
+            Ok(handles) => ActionHandles { _handles: handles },
+            Err(error) => {
+                warn!("Could not load the actions folder: {error:?}");
+                ActionHandles {
+                    _handles: Vec::new(),
+                }
+            }
+        }
+    }
+}
+
+/// Adds actions to [`Actions`] as they are loaded, and replaces them when their files change
+fn sync_action_assets(
+    mut asset_events: EventReader<AssetEvent<ActionData>>,
+    action_assets: Res<Assets<ActionData>>,
+    mut actions: ResMut<Actions>,
+) {
+    for event in asset_events.iter() {
+        let handle = match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => handle,
+            // Removed actions stay usable until the game is restarted
+            AssetEvent::Removed { .. } => continue,
+        };
+
+        if let Some(data) = action_assets.get(handle) {
+            // Replacing the queued action would lose track of its progress
+            if actions.current() == Some(data.id()) {
+                warn!(
+                    "{} was not reloaded, as it is currently being used.",
+                    data.name
+                );
+                continue;
+            }
+
+            info!("Loaded the {} action.", data.name);
+            actions.insert(data.to_action());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An action that flips a coin, then runs the `steps`
+    fn action(steps: &str) -> Result<ActionData, ActionDataError> {
+        ActionData::from_ron(&format!(
+            "(name: \"Test\", steps: [Flip(damage: 1), {steps}])"
+        ))
+    }
+
+    #[test]
+    fn names_of_built_in_actions_are_rejected() {
+        for name in ["Attack", "flee", "F_LEE"] {
+            let result =
+                ActionData::from_ron(&format!("(name: \"{name}\", steps: [Flip(damage: 1)])"));
+
+            assert!(
+                matches!(&result, Err(ActionDataError::ReservedName(reserved)) if reserved == name),
+                "{name} should be reserved"
+            );
+        }
+    }
+
+    #[test]
+    fn repeats_up_to_the_cap_are_accepted() {
+        let data = action("Repeat(times: 256, steps: [Flip(damage: 1)])").unwrap();
+
+        assert_eq!(data.to_action().max_draws(), Some(257));
+    }
+
+    #[test]
+    fn repeats_over_the_cap_are_rejected() {
+        let result = action("Repeat(times: 257, steps: [Flip(damage: 1)])");
+
+        assert!(matches!(
+            result,
+            Err(ActionDataError::TooManyRepeats { times: 257 })
+        ));
+    }
+
+    #[test]
+    fn nested_repeats_over_the_step_cap_are_rejected() {
+        let data =
+            action("Repeat(times: 4, steps: [Repeat(times: 255, steps: [Flip(damage: 1)])])")
+                .unwrap();
+        assert_eq!(data.to_action().max_draws(), Some(1021));
+
+        let result = action(
+            "Repeat(times: 256, steps: [Repeat(times: 256, steps: [Repeat(times: 256, steps: [Flip(damage: 1)])])])",
+        );
+        assert!(matches!(
+            result,
+            Err(ActionDataError::TooManySteps { steps: 16_777_217 })
+        ));
+    }
+
+    #[test]
+    fn checks_can_jump_to_later_labels() {
+        let data = action(
+            r#"Check(threshold: 128, otherwise: Some("miss")), Flip(damage: 1), Label("miss"), Kill"#,
+        )
+        .unwrap();
+
+        assert_eq!(data.to_action().max_draws(), Some(3));
+    }
+
+    #[test]
+    fn jumps_to_unknown_labels_are_rejected() {
+        let result = action(r#"Check(threshold: 128, otherwise: Some("miss")), Kill"#);
+
+        assert!(matches!(result, Err(ActionDataError::UnknownLabel(label)) if label == "miss"));
+    }
+
+    #[test]
+    fn jumps_backwards_are_rejected() {
+        let result = action(r#"Label("again"), Check(threshold: 128, otherwise: Some("again"))"#);
+
+        assert!(matches!(result, Err(ActionDataError::UnknownLabel(label)) if label == "again"));
+    }
+
+    #[test]
+    fn labels_in_repeats_are_rejected_as_duplicates() {
+        let result = action(
+            r#"Repeat(times: 2, steps: [Check(threshold: 128, otherwise: Some("tails")), Label("tails")])"#,
+        );
+
+        assert!(matches!(result, Err(ActionDataError::DuplicateLabel(label)) if label == "tails"));
+    }
+}
@@ -0,0 +1,35 @@
+use super::Commandlike;
+use crate::actions::{Action, ActionData, Cost, Step, Targeting};
+use leafwing_terminal::TerminalCommand;
+
+/// Tries to escape from combat
+#[derive(TerminalCommand)]
+#[terminal_command(name = "flee")]
+pub(super) struct FleeCommand;
+
+impl Commandlike for FleeCommand {
+    fn target(&self) -> Option<&str> {
+        None
+    }
+}
+
+/// The number of action points spent on each attempt to flee
+const FLEE_COST: u8 = 1;
+
+impl Action {
+    /// Creates a new [`Action`] that corresponds to a [`FleeCommand`]
+    ///
+    /// Fleeing uses a single RNG value, and succeeds if the value is within the user's [`FleeChance`](crate::combat_statistics::FleeChance).
+    pub fn flee() -> Action {
+        ActionData {
+            name: "Flee".to_string(),
+            cost: Cost {
+                ap: FLEE_COST,
+                ..Cost::default()
+            },
+            targeting: Targeting::SelfOnly,
+            steps: vec![Step::Flee],
+        }
+        .to_action()
+    }
+}
@@ -0,0 +1,121 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use std::hash::{Hash, Hasher};
+
+/// Identifies an [`Action`](super::Action)
+///
+/// Built-in actions have a variant of their own,
+/// while actions loaded from [`ActionData`](super::ActionData) assets are identified by their name.
+/// [`ActionId::Data`] is the only open-ended variant: data actions cannot use the name of a built-in action.
+/// Data actions are compared and hashed ignoring case, spaces and underscores, so that "Fire Bolt" and "fire_bolt" are the same action.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ActionId {
+    /// A basic weapon attack, created by [`Action::attack`](super::Action::attack)
+    Attack,
+    /// An attempt to escape from combat, created by [`Action::flee`](super::Action::flee)
+    Flee,
+    /// An action loaded from an asset, with this name
+    ///
+    /// Names that [`ActionId::from_name`] resolves to a built-in action are rejected when the asset loads.
+    Data(String),
+}
+
+impl ActionId {
+    /// Identifies the action with the `name`
+    ///
+    /// Built-in actions are matched ignoring case, spaces and underscores: any other name identifies a data action.
+    #[must_use]
+    pub fn from_name(name: &str) -> Self {
+        match normalize(name).as_str() {
+            "attack" => ActionId::Attack,
+            "flee" => ActionId::Flee,
+            _ => ActionId::Data(name.to_string()),
+        }
+    }
+
+    /// Does the `name` refer to this action, ignoring case, spaces and underscores?
+    #[must_use]
+    pub fn matches(&self, name: &str) -> bool {
+        normalize(&self.to_string()) == normalize(name)
+    }
+}
+
+impl PartialEq for ActionId {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (ActionId::Attack, ActionId::Attack) | (ActionId::Flee, ActionId::Flee) => true,
+            (ActionId::Data(name), ActionId::Data(other_name)) => {
+                normalize(name) == normalize(other_name)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Eq for ActionId {}
+
+// Must agree with `PartialEq`, so that equal data actions are stored under the same key
+impl Hash for ActionId {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+
+        if let ActionId::Data(name) = self {
+            normalize(name).hash(state);
+        }
+    }
+}
+
+impl Display for ActionId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ActionId::Attack => f.write_str("Attack"),
+            ActionId::Flee => f.write_str("Flee"),
+            ActionId::Data(name) => f.write_str(name),
+        }
+    }
+}
+
+impl From<&str> for ActionId {
+    fn from(name: &str) -> Self {
+        ActionId::from_name(name)
+    }
+}
+
+/// Lowercases the `name`, and removes any spaces and underscores
+pub(super) fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(|c| !matches!(c, ' ' | '_'))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::utils::HashSet;
+
+    #[test]
+    fn data_actions_ignore_case_spaces_and_underscores() {
+        assert_eq!(
+            ActionId::Data("Fire Bolt".to_string()),
+            ActionId::Data("fire_bolt".to_string())
+        );
+        assert_ne!(
+            ActionId::Data("Fire Bolt".to_string()),
+            ActionId::Data("Ice Bolt".to_string())
+        );
+
+        let set: HashSet<ActionId> = ["Fire Bolt", "fire bolt", "FIREBOLT"]
+            .into_iter()
+            .map(ActionId::from_name)
+            .collect();
+        assert_eq!(set.len(), 1);
+    }
+
+    #[test]
+    fn built_in_actions_are_distinct() {
+        assert_eq!(ActionId::from_name("ATTACK"), ActionId::Attack);
+        assert_ne!(ActionId::Attack, ActionId::Flee);
+        assert_ne!(ActionId::Attack, ActionId::Data("Attack".to_string()));
+    }
+}
@@ -1,85 +1,380 @@
 //! Actions that can be used by both players and monsters
 
+use crate::combat_flow::{Active, Fled};
+use crate::combat_statistics::{ActionPoints, CreatureResources, Life, Mana, Resource as _};
+use crate::creatures::{Monster, Player};
+use crate::prediction::{Annotation, PredictionContext, Predictor};
+use crate::spells::Spellbook;
+use crate::status_effects::roll_confusion;
 use crate::system_sequence::SystemSeq;
-use bevy::ecs::system::Resource;
+use bevy::ecs::system::{Resource, SystemParam};
 use bevy::prelude::*;
 use bevy::utils::HashMap;
 use leafwing_terminal::*;
+use std::fmt::Display;
+use std::sync::Arc;
 
 mod available_actions;
-pub use available_actions::AvailableActions;
+pub use available_actions::{AvailableActions, ForgetAction, LearnAction};
 
 mod attack;
 use attack::*;
 
+mod context;
+pub use context::{ActionContext, RollResult};
+
+mod cost;
+pub use cost::{Cost, CostKind};
+
+mod data;
+pub use data::{ActionAssetPlugin, ActionData, ActionDataError};
+
+mod flee;
+use flee::*;
+
+mod id;
+pub use id::ActionId;
+
+mod steps;
+pub use steps::{build_steps, predict_steps, DamageRange, Form, Step};
+
+mod targeting;
+pub use targeting::Targeting;
+use targeting::{Combatant, TargetingError};
+
 /// Adds [`TerminalCommands`](TerminalCommand) and [`Actions`](Action) for all of the available actions
+///
+/// Terminal commands are only added if the [`TerminalPlugin`] was added first.
+/// Any action that the player knows can be used with the `use <action> [target]` command, including those added by the [`ActionAssetPlugin`].
+/// Targets are named by their [`Name`], so `use attack goblin2` attacks "Goblin 2".
+/// The player's known actions can be listed with `actions`.
 pub struct ActionPlugin;
 
 impl Plugin for ActionPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<Actions>()
-            .add_action::<AttackCommand>(Action::attack());
+            .add_event::<LearnAction>()
+            .add_event::<ForgetAction>()
+            .add_system(available_actions::learn_and_forget_actions)
+            .add_action::<AttackCommand>(Action::attack())
+            .add_action::<FleeCommand>(Action::flee());
+
+        if app.world.contains_resource::<TerminalConfiguration>() {
+            app.add_terminal_command::<UseCommand, _, _>(use_command)
+                .add_terminal_command::<available_actions::ActionsCommand, _, _>(
+                available_actions::actions_command,
+            );
+        }
     }
 }
 
+/// Adds the `learn` and `forget` terminal commands, which change the actions that the player knows
+///
+/// These are intended for debugging, as they ignore the usual rules of the game.
+/// The [`ActionPlugin`] must also be added, as it applies the [`LearnAction`] and [`ForgetAction`] events that the commands send.
+pub struct LearnPlugin;
+
+impl Plugin for LearnPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_terminal_command::<available_actions::LearnCommand, _, _>(
+            available_actions::learn_command,
+        )
+        .add_terminal_command::<available_actions::ForgetCommand, _, _>(
+            available_actions::forget_command,
+        );
+    }
+}
+
+/// Uses the action with the provided name, optionally on the creature with the `target` name
+#[derive(TerminalCommand)]
+#[terminal_command(name = "use")]
+struct UseCommand {
+    /// The name of the action, ignoring case, spaces and underscores
+    action: String,
+    /// The name of the creature to use the action on, ignoring case, spaces and underscores
+    target: Option<String>,
+}
+
+fn use_command(mut terminal_command: TerminalCommand<UseCommand>, mut action_queue: ActionQueue) {
+    if let Some(UseCommand { action, target }) = terminal_command.take() {
+        let result = action_queue.resolve(&action).and_then(|action_id| {
+            queue_for_player(&mut action_queue, action_id, target.as_deref())
+        });
+
+        if let Err(error) = result {
+            terminal_command.reply(error.to_string());
+        }
+    }
+}
+
+/// Queues the action with the `action_id` for the player, on the creature named `target` if any
+fn queue_for_player(
+    action_queue: &mut ActionQueue,
+    action_id: ActionId,
+    target: Option<&str>,
+) -> Result<(), ActionError> {
+    let target = target
+        .map(|target| action_queue.resolve_target(target))
+        .transpose()?;
+
+    action_queue.queue(action_id, target)
+}
+
 /// An action that can be applied to the [`World`] in a step-by-step fashion
 // TODO: refactor to move Systems out into Actions
-// TODO: refactor to be a mega-enum
+///
+/// Cloning an action creates a fresh copy of its systems, which can be used on a different [`World`].
+/// The copy keeps the user and targets that the action was queued with.
 pub struct Action {
-    name: String,
+    id: ActionId,
+    build: Arc<dyn Fn() -> SystemSeq + Send + Sync>,
     systems: SystemSeq,
+    predictor: Option<Predictor>,
+    cost: Cost,
+    max_draws: Option<usize>,
+    targeting: Targeting,
+    spell: bool,
+    context: ActionContext,
 }
 
 impl Action {
-    /// Creates a new [`Action`], whose `systems` will be applied to the [`World`] one step at a time
-    pub fn new(name: impl Into<String>, systems: SystemSeq) -> Self {
+    /// Creates a new [`Action`], whose systems will be applied to the [`World`] one step at a time
+    ///
+    /// The systems are created by calling `build`, which is called again each time the action is cloned.
+    pub fn new(
+        id: impl Into<ActionId>,
+        build: impl Fn() -> SystemSeq + Send + Sync + 'static,
+    ) -> Self {
         Action {
-            name: name.into(),
-            systems: systems,
+            id: id.into(),
+            systems: build(),
+            build: Arc::new(build),
+            predictor: None,
+            cost: Cost::default(),
+            max_draws: None,
+            targeting: Targeting::default(),
+            spell: false,
+            context: ActionContext::default(),
         }
     }
 
-    /// The name of the action
+    /// Sets the [`Predictor`] used to show the player what this action will do with the upcoming RNG values
+    #[must_use]
+    pub fn with_predictor(
+        mut self,
+        predictor: impl Fn(&PredictionContext, &[u8]) -> Vec<Annotation> + Send + Sync + 'static,
+    ) -> Self {
+        self.predictor = Some(Arc::new(predictor));
+        self
+    }
+
+    /// The [`Predictor`] for this action, if it has one
+    pub fn predictor(&self) -> Option<Predictor> {
+        self.predictor.clone()
+    }
+
+    /// Sets the [`Cost`] paid to use this action
+    #[must_use]
+    pub fn with_cost(mut self, cost: Cost) -> Self {
+        self.cost = cost;
+        self
+    }
+
+    /// The [`Cost`] paid to use this action
+    #[must_use]
+    pub fn cost(&self) -> Cost {
+        self.cost
+    }
+
+    /// Sets the largest number of RNG values that this action can use, shown to the player
+    #[must_use]
+    pub fn with_max_draws(mut self, max_draws: usize) -> Self {
+        self.max_draws = Some(max_draws);
+        self
+    }
+
+    /// The largest number of RNG values that this action can use, if it is known
+    #[must_use]
+    pub fn max_draws(&self) -> Option<usize> {
+        self.max_draws
+    }
+
+    /// Sets which creatures this action can be used on
+    #[must_use]
+    pub fn with_targeting(mut self, targeting: Targeting) -> Self {
+        self.targeting = targeting;
+        self
+    }
+
+    /// Which creatures this action can be used on
+    #[must_use]
+    pub fn targeting(&self) -> Targeting {
+        self.targeting
+    }
+
+    /// Marks this action as a spell, which is cast from a creature's [`Spellbook`] rather than their [`AvailableActions`]
+    #[must_use]
+    pub fn as_spell(mut self) -> Self {
+        self.spell = true;
+        self
+    }
+
+    /// Is this action a spell?
+    #[must_use]
+    pub fn is_spell(&self) -> bool {
+        self.spell
+    }
+
+    /// The identifier of the action
     ///
     /// This is immutable after creation.
+    #[must_use]
+    pub fn id(&self) -> ActionId {
+        self.id.clone()
+    }
+
+    /// The name of the action, as shown to the player
+    #[must_use]
     pub fn name(&self) -> String {
-        self.name.clone()
+        self.id.to_string()
+    }
+
+    /// The state shared between the steps of this action
+    #[must_use]
+    pub fn context(&self) -> &ActionContext {
+        &self.context
     }
 
     /// Applies the next step of the action to the [`World`], according to the provided vector of `systems`
+    ///
+    /// The [`Cost`] is paid when the first step is applied,
+    /// and a confused actor rolls to turn an action aimed at their enemies on themselves.
+    /// Actions that were not queued with [`Actions::queue`] are used by the [`Active`] creature on its enemies.
+    /// The [`ActionContext`] is available to each step as a resource while it runs.
     pub fn advance(&mut self, world: &mut World) {
+        if self.is_finished() {
+            return;
+        }
+
+        if !self.has_started() {
+            if self.context.actor.is_none() {
+                self.context = ActionContext::for_active_creature(world, self.name());
+            }
+
+            if let Some(actor) = self.context.actor {
+                // The creature's resources may have changed since the action was queued
+                if let Err(error) = self.cost.pay(world, actor) {
+                    if let Some(mut lines) = world.get_resource_mut::<Events<PrintTerminalLine>>() {
+                        lines.send(PrintTerminalLine::new(error.to_string()));
+                    }
+
+                    self.context.abort();
+                    return;
+                }
+
+                if self.targeting.targets_enemies() && roll_confusion(world, actor) {
+                    self.context.targets = vec![actor];
+                }
+            }
+        }
+
+        world.insert_resource(std::mem::take(&mut self.context));
         self.systems.run_next(world);
+        self.context = world
+            .remove_resource::<ActionContext>()
+            .expect("Steps of an action must not remove the `ActionContext`.");
+    }
+
+    /// Has the first step of the action been applied?
+    pub fn has_started(&self) -> bool {
+        self.systems.index() > 0
     }
 
-    /// Is the action out of systems?
+    /// Is the action out of systems, or was it aborted?
     pub fn is_finished(&self) -> bool {
-        self.systems.is_finished()
+        self.systems.is_finished() || self.context.is_aborted()
     }
 
-    /// Resets the pattern of applied `systems` to the beginning of the supplied list
+    /// Resets the pattern of applied `systems` to the beginning of the supplied list, and clears the [`ActionContext`]
+    ///
+    /// The action's user and targets are cleared too, so it must be queued again before it is next used.
     pub fn reset(&mut self) {
         self.systems.reset();
+        self.context = ActionContext::default();
+    }
+}
+
+impl Clone for Action {
+    fn clone(&self) -> Self {
+        Action {
+            id: self.id.clone(),
+            build: self.build.clone(),
+            systems: (self.build)(),
+            predictor: self.predictor.clone(),
+            cost: self.cost,
+            max_draws: self.max_draws,
+            targeting: self.targeting,
+            spell: self.spell,
+            context: self.context.restarted(),
+        }
     }
 }
 
 /// The total list of available [`Action`], stored as a resource
 #[derive(Default)]
 pub struct Actions {
-    current: Option<String>,
-    map: HashMap<String, Action>,
+    current: Option<ActionId>,
+    map: HashMap<ActionId, Action>,
 }
 
 impl Actions {
     /// Gets the current action
-    pub fn current(&self) -> Option<String> {
+    pub fn current(&self) -> Option<ActionId> {
         self.current.clone()
     }
 
-    /// Sets the current action
-    pub fn set_current(&mut self, action_name: String) {
-        assert!(self.map.contains_key(&action_name));
+    /// Sets the current action, to be used by the `user` on the `targets`
+    ///
+    /// Fails if no such action exists, or if the user cannot afford its [`Cost`] with their `resources`.
+    pub fn set_current(
+        &mut self,
+        action_id: ActionId,
+        user: Entity,
+        targets: Vec<Entity>,
+        resources: &CreatureResources,
+    ) -> Result<(), ActionError> {
+        let action = self.get_mut(&action_id)?;
+        action.cost().check(resources)?;
+        action.context = ActionContext::new(action.name(), user, targets);
+
+        self.current = Some(action_id);
+        Ok(())
+    }
+
+    /// Queues the action with the `action_id`, to be used by the `user` on the `targets`
+    ///
+    /// Fails if another action is already queued, or for any of the reasons that [`Actions::set_current`] fails.
+    pub fn queue(
+        &mut self,
+        action_id: ActionId,
+        user: Entity,
+        targets: Vec<Entity>,
+        resources: &CreatureResources,
+    ) -> Result<(), ActionError> {
+        if self.current.is_some() {
+            return Err(ActionError::AlreadyQueued);
+        }
+
+        self.set_current(action_id, user, targets, resources)
+    }
 
-        self.current = Some(action_name);
+    /// Finds the identifier of the stored action whose name matches `action_name`, ignoring case, spaces and underscores
+    #[must_use]
+    pub fn resolve(&self, action_name: &str) -> Option<ActionId> {
+        self.map
+            .keys()
+            .find(|action_id| action_id.matches(action_name))
+            .cloned()
     }
 
     /// Clears the current action
@@ -87,29 +382,146 @@ impl Actions {
         self.current = None;
     }
 
-    /// Inserts an [`Action`] into this collection
+    /// Inserts an [`Action`] into this collection, replacing any action with the same [`ActionId`]
     pub fn insert(&mut self, action: Action) {
-        self.map.insert(action.name(), action);
+        self.map.insert(action.id(), action);
+    }
+
+    /// Describes each action whose [`ActionId`] passes the `filter`, in alphabetical order
+    ///
+    /// Each line gives the action's cost and how many RNG values it uses,
+    /// and notes when a creature with the `resources` cannot afford it.
+    #[must_use]
+    pub fn describe(
+        &self,
+        resources: &CreatureResources,
+        filter: impl Fn(&ActionId) -> bool,
+    ) -> Vec<String> {
+        let mut actions: Vec<(&ActionId, &Action)> = self
+            .map
+            .iter()
+            .filter(|(action_id, _)| filter(action_id))
+            .collect();
+        actions.sort_by_key(|(action_id, _)| action_id.to_string());
+
+        actions
+            .into_iter()
+            .map(|(action_id, action)| {
+                let draws = match action.max_draws() {
+                    Some(max_draws) => format!("up to {max_draws} RNG"),
+                    None => "unknown RNG".to_string(),
+                };
+
+                let affordable = if action.cost().check(resources).is_ok() {
+                    ""
+                } else {
+                    " (cannot afford)"
+                };
+
+                format!(
+                    "{action_id}: {}, {draws}{affordable}",
+                    action.cost().describe()
+                )
+            })
+            .collect()
     }
 
-    /// Gets an immutable mutable reference to the underlying [`Action`] with the `action_name`
-    pub fn get(&self, action_name: String) -> &Action {
+    /// Gets a reference to the underlying [`Action`] with the `action_id`
+    pub fn get(&self, action_id: &ActionId) -> Result<&Action, ActionError> {
         self.map
-            .get(&action_name)
-            .unwrap_or_else(|| panic!("No action named {action_name} was found in `Actions`."))
+            .get(action_id)
+            .ok_or_else(|| ActionError::Unknown(action_id.to_string()))
     }
 
-    /// Gets a mutable reference to the underlying [`Action`] with the `action_name`
-    pub fn get_mut(&mut self, action_name: String) -> &mut Action {
+    /// Gets a mutable reference to the underlying [`Action`] with the `action_id`
+    pub fn get_mut(&mut self, action_id: &ActionId) -> Result<&mut Action, ActionError> {
         self.map
-            .get_mut(&action_name)
-            .unwrap_or_else(|| panic!("No action named {action_name} was found in `Actions`."))
+            .get_mut(action_id)
+            .ok_or_else(|| ActionError::Unknown(action_id.to_string()))
     }
 }
 
-trait Commandlike: Resource + CommandName + CommandArgs + CommandHelp {}
+/// The reasons that an [`Action`] could not be used
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ActionError {
+    /// Another action is already being applied
+    AlreadyQueued,
+    /// No action with this name exists
+    Unknown(String),
+    /// The creature does not know this action
+    Unavailable(ActionId),
+    /// The action is not a spell, so cannot be cast
+    NotASpell(ActionId),
+    /// The spell is not in the creature's [`Spellbook`]
+    UnknownSpell(ActionId),
+    /// The creature cannot act, as it is not their turn
+    NotYourTurn,
+    /// No creature with this name exists
+    UnknownTarget(String),
+    /// The action cannot be used on the creature with this name
+    InvalidTarget {
+        /// The action being used
+        action: ActionId,
+        /// The name of the creature it was used on
+        target: String,
+    },
+    /// There are no creatures that the action can be used on
+    NoTargets(ActionId),
+    /// The creature does not have enough of a resource to pay the action's [`Cost`]
+    CannotAfford {
+        /// The resource that is lacking
+        resource: CostKind,
+        /// How much of the resource is needed
+        required: u8,
+        /// How much of the resource the creature has
+        available: u8,
+    },
+}
+
+impl Display for ActionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ActionError::AlreadyQueued => f.write_str("Another action is already queued."),
+            ActionError::Unknown(action_name) => {
+                f.write_fmt(format_args!("There is no action named {action_name}."))
+            }
+            ActionError::Unavailable(action_id) => {
+                f.write_fmt(format_args!("{action_id} has not been learned."))
+            }
+            ActionError::NotASpell(action_id) => {
+                f.write_fmt(format_args!("{action_id} is not a spell."))
+            }
+            ActionError::UnknownSpell(action_id) => {
+                f.write_fmt(format_args!("{action_id} is not in the spellbook."))
+            }
+            ActionError::NotYourTurn => f.write_str("Creatures can only act on their own turn."),
+            ActionError::UnknownTarget(target) => {
+                f.write_fmt(format_args!("There is no creature named {target}."))
+            }
+            ActionError::InvalidTarget { action, target } => {
+                f.write_fmt(format_args!("{action} cannot be used on {target}."))
+            }
+            ActionError::NoTargets(action) => {
+                f.write_fmt(format_args!("There is no one to use {action} on."))
+            }
+            ActionError::CannotAfford {
+                resource,
+                required,
+                available,
+            } => f.write_fmt(format_args!(
+                "Using this takes {required} {resource}, but only {available} is left."
+            )),
+        }
+    }
+}
+
+impl std::error::Error for ActionError {}
 
-impl<T: Resource + CommandName + CommandArgs + CommandHelp> Commandlike for T {}
+/// A terminal command that uses an action, optionally naming the creature to use it on
+trait Commandlike: Resource + CommandName + CommandArgs + CommandHelp {
+    /// The name of the creature to use the action on, if one was given
+    fn target(&self) -> Option<&str>;
+}
 
 trait ActionExt {
     fn add_action<TC: Commandlike>(&mut self, action: Action);
@@ -117,8 +529,10 @@ trait ActionExt {
 
 impl ActionExt for App {
     fn add_action<TC: Commandlike>(&mut self, action: Action) {
-        // Register a system to listen for the TC terminal command
-        self.add_terminal_command::<TC, _, _>(create_start_action_system::<TC>(action.name()));
+        // Register a system to listen for the TC terminal command, if there is a terminal to type it into
+        if self.world.contains_resource::<TerminalConfiguration>() {
+            self.add_terminal_command::<TC, _, _>(create_start_action_system::<TC>(action.id()));
+        }
         // Add the action to the Actions collection
         let mut actions = self.world.get_resource_mut::<Actions>().unwrap();
         actions.insert(action);
@@ -126,18 +540,213 @@ impl ActionExt for App {
 }
 
 fn create_start_action_system<TC: Commandlike>(
-    action_name: String,
-) -> impl FnMut(TerminalCommand<TC>, ResMut<Actions>) {
-    move |mut terminal_command: TerminalCommand<TC>, mut actions: ResMut<Actions>| {
+    action_id: ActionId,
+) -> impl FnMut(TerminalCommand<TC>, ActionQueue) {
+    move |mut terminal_command: TerminalCommand<TC>, mut action_queue: ActionQueue| {
         // Break early if the command was not entered or was malformed
-        if terminal_command.take().is_none() {
-            return;
+        let command = match terminal_command.take() {
+            Some(command) => command,
+            None => return,
+        };
+
+        if let Err(error) = queue_for_player(&mut action_queue, action_id.clone(), command.target())
+        {
+            terminal_command.reply(error.to_string());
+        }
+    }
+}
+
+/// Queues actions on behalf of [`Active`] creatures, checking that they know them, can afford them and can use them on their targets
+#[derive(SystemParam)]
+pub struct ActionQueue<'w, 's> {
+    actions: ResMut<'w, Actions>,
+    active_query: Query<
+        'w,
+        's,
+        (
+            Option<&'static Life>,
+            Option<&'static Mana>,
+            Option<&'static ActionPoints>,
+            Option<&'static AvailableActions>,
+            Option<&'static Spellbook>,
+        ),
+        With<Active>,
+    >,
+    player_query: Query<'w, 's, Entity, (With<Player>, With<Active>)>,
+    combatant_query: Query<
+        'w,
+        's,
+        (
+            Entity,
+            Option<&'static Name>,
+            Option<&'static Player>,
+            Option<&'static Life>,
+        ),
+        (Or<(With<Player>, With<Monster>)>, Without<Fled>),
+    >,
+}
+
+impl<'w, 's> ActionQueue<'w, 's> {
+    /// The identifier of the queued action, if any
+    #[must_use]
+    pub fn current(&self) -> Option<ActionId> {
+        self.actions.current()
+    }
+
+    /// Has an action been queued, advanced or cleared since the system using this last ran?
+    #[must_use]
+    pub fn is_changed(&self) -> bool {
+        self.actions.is_changed()
+    }
+
+    /// Finds the identifier of the action whose name matches `action_name`, ignoring case, spaces and underscores
+    pub fn resolve(&self, action_name: &str) -> Result<ActionId, ActionError> {
+        self.actions
+            .resolve(action_name)
+            .ok_or_else(|| ActionError::Unknown(action_name.to_string()))
+    }
+
+    /// Finds the creature whose [`Name`] matches `target_name`, ignoring case, spaces and underscores
+    pub fn resolve_target(&self, target_name: &str) -> Result<Entity, ActionError> {
+        let normalized = id::normalize(target_name);
+
+        self.combatant_query
+            .iter()
+            .find(|(_, name, ..)| {
+                name.map_or(false, |name| id::normalize(name.as_str()) == normalized)
+            })
+            .map(|(entity, ..)| entity)
+            .ok_or_else(|| ActionError::UnknownTarget(target_name.to_string()))
+    }
+
+    /// Queues the action with the `action_id`, to be used by the player on the `target`
+    ///
+    /// Fails if it is not the player's turn, or for any of the reasons that [`ActionQueue::queue_for`] fails.
+    pub fn queue(
+        &mut self,
+        action_id: ActionId,
+        target: Option<Entity>,
+    ) -> Result<(), ActionError> {
+        let player = self
+            .player_query
+            .get_single()
+            .map_err(|_| ActionError::NotYourTurn)?;
+
+        self.queue_for(player, action_id, target)
+    }
+
+    /// Queues the spell with the `spell_id`, to be cast by the player on the `target`
+    ///
+    /// Fails if the action is not a spell, or for any of the reasons that [`ActionQueue::queue`] fails.
+    pub fn cast(&mut self, spell_id: ActionId, target: Option<Entity>) -> Result<(), ActionError> {
+        if !self.actions.get(&spell_id)?.is_spell() {
+            return Err(ActionError::NotASpell(spell_id));
+        }
+
+        self.queue(spell_id, target)
+    }
+
+    /// Queues the action with the `action_id`, to be used by the `user` on the `target`
+    ///
+    /// If no `target` is given, one is chosen according to the action's [`Targeting`].
+    /// Fails if the user is not [`Active`], if the action is not in their [`AvailableActions`]
+    /// (or their [`Spellbook`], for spells), if it cannot be used on the `target`,
+    /// or for any of the reasons that [`Actions::queue`] fails.
+    pub fn queue_for(
+        &mut self,
+        user: Entity,
+        action_id: ActionId,
+        target: Option<Entity>,
+    ) -> Result<(), ActionError> {
+        if self.actions.current().is_some() {
+            return Err(ActionError::AlreadyQueued);
+        }
+
+        let (resources, available_actions, spellbook) = match self.active_query.get(user) {
+            Ok((life, mana, action_points, available_actions, spellbook)) => (
+                CreatureResources::new(life, mana, action_points),
+                available_actions,
+                spellbook,
+            ),
+            Err(_) => return Err(ActionError::NotYourTurn),
+        };
+
+        let action = self.actions.get(&action_id)?;
+        let targeting = action.targeting();
+
+        if action.is_spell() {
+            if !spellbook.map_or(false, |spellbook| spellbook.contains(&action_id)) {
+                return Err(ActionError::UnknownSpell(action_id));
+            }
+        } else if !available_actions.map_or(false, |available_actions| {
+            available_actions.contains(&action_id)
+        }) {
+            return Err(ActionError::Unavailable(action_id));
         }
 
-        if let Some(_action_name) = actions.current() {
-            terminal_command.reply("You cannot use actions when another action is queued.");
-        } else {
-            actions.set_current(action_name.clone());
+        // Defeated creatures cannot be targeted
+        let combatants: Vec<Combatant> = self
+            .combatant_query
+            .iter()
+            .filter(|(.., life)| life.map_or(true, |life| life.current() > 0))
+            .map(|(entity, _, player, _)| Combatant {
+                entity,
+                is_player: player.is_some(),
+            })
+            .collect();
+        let user = Combatant {
+            entity: user,
+            is_player: self.player_query.get(user).is_ok(),
+        };
+
+        let targets = targeting
+            .select(user, target, &combatants)
+            .map_err(|error| match error {
+                TargetingError::Invalid => ActionError::InvalidTarget {
+                    action: action_id.clone(),
+                    target: target.map_or_else(String::new, |target| self.name(target)),
+                },
+                TargetingError::NoTargets => ActionError::NoTargets(action_id.clone()),
+            })?;
+
+        self.actions
+            .queue(action_id, user.entity, targets, &resources)
+    }
+
+    /// The name of the `entity`, for use in messages
+    fn name(&self, entity: Entity) -> String {
+        match self.combatant_query.get(entity) {
+            Ok((_, Some(name), ..)) => name.as_str().to_string(),
+            _ => "that creature".to_string(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn descriptions_are_filtered_and_sorted() {
+        let mut actions = Actions::default();
+        actions.insert(Action::flee());
+        actions.insert(Action::attack());
+        let resources = CreatureResources {
+            life: Some(10),
+            mana: Some(10),
+            action_points: Some(1),
+        };
+
+        assert_eq!(
+            actions.describe(&resources, |_| true),
+            [
+                "Attack: 2 AP, up to 4 RNG (cannot afford)",
+                "Flee: 1 AP, up to 1 RNG"
+            ]
+        );
+        assert_eq!(
+            actions.describe(&resources, |action_id| *action_id == ActionId::Flee),
+            ["Flee: 1 AP, up to 1 RNG"]
+        );
+    }
+}
@@ -0,0 +1,1388 @@
+use crate::actions::{ActionContext, AvailableActions};
+use crate::combat_flow::Fled;
+use crate::combat_statistics::{
+    CritChance, Damage, DodgeChance, FleeChance, Intelligence, Life, Mana, Resource, SpellSuccess,
+};
+use crate::prediction::{Annotation, Outcome, PredictionContext, Revealed, RollPart, Stat};
+use crate::rng::{draw_roll, Advantage, DrawLabel, RNGOutputs, Rng, RngLog, RollKind, RollMode};
+use crate::spells::Spellbook;
+use crate::status_effects::{
+    EffectCategory, EffectKind, EffectRemoved, RemovalCause, StatusEffect, StatusEffects,
+};
+use crate::system_sequence::{Flow, SystemSeq};
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use leafwing_terminal::PrintTerminalLine;
+use serde::{Deserialize, Serialize};
+
+/// A single step of an action, which uses one RNG value unless otherwise noted
+///
+/// Steps are the building blocks of [`ActionData`](super::ActionData),
+/// and are applied to the creatures stored in the [`ActionContext`].
+/// Damage, healing and status effect steps affect every target, while the other steps roll against the first target.
+/// Creatures with [`Advantage`] use more RNG values for each roll, combining them into one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[allow(missing_docs)]
+pub enum Step {
+    /// The actor rolls to hit the target
+    Hit,
+    /// The target rolls to dodge, opposing the hit roll plus the `hit_bonus`
+    ///
+    /// Stops the action if the attack is dodged.
+    Dodge { hit_bonus: u8 },
+    /// The actor rolls to land a critical hit, using their [`CritChance`]
+    ///
+    /// A critical hit multiplies the damage dealt by later steps by the `multiplier`.
+    /// If damage was already rolled, the extra damage is dealt immediately.
+    Crit { multiplier: u8 },
+    /// The actor rolls damage, which is dealt to each target
+    Damage(DamageRange),
+    /// The actor rolls healing in the range from `min` to `max`, which restores the life of each target
+    Heal { min: u8, max: u8 },
+    /// Each target loses exactly this much life
+    ///
+    /// Uses no RNG values.
+    LoseLife(u8),
+    /// Each target regains exactly this much life
+    ///
+    /// Uses no RNG values.
+    GainLife(u8),
+    /// Each target is reduced to 0 life
+    ///
+    /// Uses no RNG values.
+    Kill,
+    /// The actor rolls an amount in the range from `min` to `max`, which is drained from the mana of each target
+    DrainMana { min: u8, max: u8 },
+    /// The actor flips a coin, dealing `damage` to each target on heads
+    ///
+    /// Values of 128 or more are heads.
+    Flip { damage: u8 },
+    /// The actor rolls to choose one of the `forms`, which each target is turned into
+    ///
+    /// Polymorphed creatures have the life of their new form, and forget all of their actions.
+    Polymorph { forms: Vec<Form> },
+    /// Reveals every stat of each target to the player
+    ///
+    /// Uses no RNG values.
+    Reveal,
+    /// The actor rolls against the `threshold`, stopping the action unless the roll is at least that high
+    ///
+    /// If `otherwise` names a later [`Step::Label`], a failed check jumps there instead of stopping.
+    Check {
+        threshold: u8,
+        #[serde(default)]
+        otherwise: Option<String>,
+    },
+    /// The actor rolls to cast a spell, which fizzles unless the roll is at least the `threshold`
+    ///
+    /// The threshold is lowered by the actor's [`Intelligence`], as described by [`SpellSuccess`].
+    /// Stops the action if the spell fizzles.
+    Cast { threshold: u8 },
+    /// The actor rolls to flee, using their [`FleeChance`]
+    ///
+    /// A successful roll removes them from combat.
+    Flee,
+    /// Gives each target the status `effect` for `duration` turns
+    ///
+    /// Uses no RNG values.
+    Effect { effect: EffectKind, duration: u8 },
+    /// Removes every effect in the `category` from each target
+    ///
+    /// Uses no RNG values.
+    Remove { category: EffectCategory },
+    /// Moves every effect in the `category` from each target to the actor
+    ///
+    /// Actors that cannot be affected by status effects steal nothing.
+    /// Uses no RNG values.
+    Steal { category: EffectCategory },
+    /// Replaces every effect in the `category` on each target with its inverse, such as bleeding with regeneration
+    ///
+    /// Uses no RNG values.
+    Invert { category: EffectCategory },
+    /// Lengthens every effect in the `category` on each target by `turns`
+    ///
+    /// Uses no RNG values.
+    Extend { category: EffectCategory, turns: u8 },
+    /// Applies the `steps` the provided number of `times`
+    ///
+    /// Uses as many RNG values as its steps do.
+    Repeat { times: usize, steps: Vec<Step> },
+    /// Marks the step after it, so that a failed [`Step::Check`] can jump there
+    ///
+    /// Uses no RNG values.
+    Label(String),
+}
+
+/// A harmless creature that a [`Step::Polymorph`] can turn its targets into
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Form {
+    /// The name of the creature, such as "sheep"
+    pub name: String,
+    /// The life that the creature has
+    pub life: u8,
+}
+
+/// How much damage is dealt by a [`Step::Damage`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[allow(missing_docs)]
+pub enum DamageRange {
+    /// Uses the actor's [`Damage`] component
+    Weapon,
+    /// Deals damage in the range from `min` to `max`
+    Fixed { min: u8, max: u8 },
+}
+
+/// The resources and events used by every [`Step`]
+#[derive(SystemParam)]
+pub(crate) struct StepParams<'w, 's> {
+    rng: ResMut<'w, Rng>,
+    rng_outputs: ResMut<'w, RNGOutputs>,
+    rng_log: ResMut<'w, RngLog>,
+    names: Query<'w, 's, &'static Name>,
+    advantage: Query<'w, 's, &'static Advantage>,
+    lines: EventWriter<'w, 's, PrintTerminalLine>,
+}
+
+impl<'w, 's> StepParams<'w, 's> {
+    /// Draws the RNG values for the `actor`'s `roll`, recording who used them for which roll
+    ///
+    /// Rolls made with advantage or disadvantage use several values, which are announced before being combined.
+    pub(crate) fn draw(&mut self, context: &ActionContext, actor: Entity, roll: RollKind) -> u8 {
+        let mode = self
+            .advantage
+            .get(actor)
+            .map_or(RollMode::SINGLE, |advantage| advantage.mode(roll));
+
+        let (values, combined) = draw_roll(
+            &mut *self.rng,
+            &mut *self.rng_outputs,
+            &mut *self.rng_log,
+            DrawLabel::new(actor, context.action.clone(), roll),
+            mode,
+        );
+
+        if mode.is_multiple() {
+            let values: Vec<String> = values.iter().map(u8::to_string).collect();
+            let line = format!(
+                "{} rolls {} values for {roll}, keeping the {}: {combined}.",
+                self.name(actor),
+                values.join(", "),
+                mode.combine
+            );
+            self.say(line);
+        }
+
+        combined
+    }
+
+    /// Prints a line to the terminal
+    pub(crate) fn say(&mut self, line: String) {
+        self.lines.send(PrintTerminalLine::new(line));
+    }
+
+    /// The name of the `entity`, for use in messages
+    pub(crate) fn name(&self, entity: Entity) -> String {
+        self.names
+            .get(entity)
+            .map_or_else(|_| "Someone".to_string(), |name| name.as_str().to_string())
+    }
+}
+
+impl Step {
+    /// Appends the systems that apply this step to the end of the `seq`
+    pub fn append_to(&self, seq: SystemSeq) -> SystemSeq {
+        match self {
+            Step::Hit => seq.then(hit),
+            Step::Dodge { hit_bonus } => {
+                let hit_bonus = *hit_bonus;
+                seq.then(
+                    move |context: ResMut<ActionContext>,
+                          params: StepParams,
+                          query: Query<(&DodgeChance, Option<&StatusEffects>)>| {
+                        dodge(hit_bonus, context, params, query)
+                    },
+                )
+            }
+            Step::Crit { multiplier } => {
+                let multiplier = *multiplier;
+                seq.then(
+                    move |context: ResMut<ActionContext>,
+                          params: StepParams,
+                          query: Query<(&CritChance, Option<&StatusEffects>)>,
+                          life_query: Query<&mut Life>| {
+                        crit(multiplier, context, params, query, life_query)
+                    },
+                )
+            }
+            Step::Damage(range) => {
+                let range = *range;
+                seq.then(
+                    move |context: ResMut<ActionContext>,
+                          params: StepParams,
+                          damage_query: Query<&mut Damage>,
+                          life_query: Query<&mut Life>| {
+                        damage(range, context, params, damage_query, life_query)
+                    },
+                )
+            }
+            Step::Heal { min, max } => {
+                let range = Damage::new(*min, *max);
+                seq.then(
+                    move |context: ResMut<ActionContext>,
+                          params: StepParams,
+                          life_query: Query<&mut Life>| {
+                        heal(&range, context, params, life_query)
+                    },
+                )
+            }
+            Step::LoseLife(amount) => {
+                let amount = *amount;
+                seq.then(
+                    move |context: ResMut<ActionContext>,
+                          params: StepParams,
+                          life_query: Query<&mut Life>| {
+                        lose_life(amount, context, params, life_query)
+                    },
+                )
+            }
+            Step::GainLife(amount) => {
+                let amount = *amount;
+                seq.then(
+                    move |context: ResMut<ActionContext>,
+                          params: StepParams,
+                          life_query: Query<&mut Life>| {
+                        gain_life(amount, context, params, life_query)
+                    },
+                )
+            }
+            Step::Kill => seq.then(kill),
+            Step::DrainMana { min, max } => {
+                let range = Damage::new(*min, *max);
+                seq.then(
+                    move |context: ResMut<ActionContext>,
+                          params: StepParams,
+                          mana_query: Query<&mut Mana>| {
+                        drain_mana(&range, context, params, mana_query)
+                    },
+                )
+            }
+            Step::Flip { damage } => {
+                let damage = *damage;
+                seq.then(
+                    move |context: ResMut<ActionContext>,
+                          params: StepParams,
+                          life_query: Query<&mut Life>| {
+                        flip(damage, context, params, life_query)
+                    },
+                )
+            }
+            Step::Polymorph { forms } => {
+                let forms = forms.clone();
+                seq.then(
+                    move |context: ResMut<ActionContext>,
+                          params: StepParams,
+                          query: Query<(
+                        &mut Life,
+                        Option<&mut AvailableActions>,
+                        Option<&mut Spellbook>,
+                    )>| { polymorph(&forms, context, params, query) },
+                )
+            }
+            Step::Reveal => seq.then(reveal),
+            Step::Check {
+                threshold,
+                otherwise,
+            } => {
+                let threshold = *threshold;
+                let target = otherwise.clone();
+                seq.then_jumping(
+                    move |context: ResMut<ActionContext>, params: StepParams| {
+                        check(threshold, target.as_deref(), context, params)
+                    },
+                    otherwise.clone(),
+                )
+            }
+            Step::Cast { threshold } => {
+                let threshold = *threshold;
+                seq.then(
+                    move |context: ResMut<ActionContext>,
+                          params: StepParams,
+                          query: Query<&Intelligence>| {
+                        cast(threshold, context, params, query)
+                    },
+                )
+            }
+            Step::Flee => seq.then(flee),
+            Step::Effect { effect, duration } => {
+                let effect = StatusEffect::new(*effect, *duration);
+                seq.then(
+                    move |context: ResMut<ActionContext>,
+                          params: StepParams,
+                          query: Query<&mut StatusEffects>| {
+                        apply_effect(effect, context, params, query)
+                    },
+                )
+            }
+            Step::Remove { category } => {
+                let category = *category;
+                seq.then(
+                    move |context: ResMut<ActionContext>,
+                          params: StepParams,
+                          query: Query<&mut StatusEffects>,
+                          removed: EventWriter<EffectRemoved>| {
+                        remove_effects(category, context, params, query, removed)
+                    },
+                )
+            }
+            Step::Steal { category } => {
+                let category = *category;
+                seq.then(
+                    move |context: ResMut<ActionContext>,
+                          params: StepParams,
+                          query: Query<&mut StatusEffects>,
+                          removed: EventWriter<EffectRemoved>| {
+                        steal_effects(category, context, params, query, removed)
+                    },
+                )
+            }
+            Step::Invert { category } => {
+                let category = *category;
+                seq.then(
+                    move |context: ResMut<ActionContext>,
+                          params: StepParams,
+                          query: Query<&mut StatusEffects>,
+                          removed: EventWriter<EffectRemoved>| {
+                        invert_effects(category, context, params, query, removed)
+                    },
+                )
+            }
+            Step::Extend { category, turns } => {
+                let (category, turns) = (*category, *turns);
+                seq.then(
+                    move |context: ResMut<ActionContext>,
+                          params: StepParams,
+                          query: Query<&mut StatusEffects>| {
+                        extend_effects(category, turns, context, params, query)
+                    },
+                )
+            }
+            Step::Repeat { times, steps } => seq.repeat(*times, || build_steps(steps)),
+            Step::Label(label) => seq.label(label.clone()),
+        }
+    }
+}
+
+/// Creates a [`SystemSeq`] that applies each of the `steps` in order
+pub fn build_steps(steps: &[Step]) -> SystemSeq {
+    steps
+        .iter()
+        .fold(SystemSeq::new(), |seq, step| step.append_to(seq))
+}
+
+/// The largest number of RNG values that the `steps` can use, when rolled without advantage
+///
+/// Actions that stop early, such as attacks that are dodged, use fewer.
+/// Each stack of [`Advantage`] or disadvantage uses one more value per roll.
+#[must_use]
+pub fn max_draws(steps: &[Step]) -> usize {
+    steps
+        .iter()
+        .map(|step| match step {
+            Step::Repeat { times, steps } => times.saturating_mul(max_draws(steps)),
+            Step::LoseLife(_)
+            | Step::GainLife(_)
+            | Step::Kill
+            | Step::Reveal
+            | Step::Effect { .. }
+            | Step::Remove { .. }
+            | Step::Steal { .. }
+            | Step::Invert { .. }
+            | Step::Extend { .. }
+            | Step::Label(_) => 0,
+            _ => 1,
+        })
+        .sum()
+}
+
+/// The actor and target of the action, printing a message if either is missing
+fn participants(context: &ActionContext, params: &mut StepParams) -> Option<(Entity, Entity)> {
+    match (context.actor, context.target()) {
+        (Some(actor), Some(target)) => Some((actor, target)),
+        _ => {
+            params.say(format!("There is no one to use {} on.", context.action));
+            None
+        }
+    }
+}
+
+fn hit(mut context: ResMut<ActionContext>, mut params: StepParams) -> Flow {
+    let (actor, target) = match participants(&context, &mut params) {
+        Some(participants) => participants,
+        None => return Flow::Abort,
+    };
+
+    let value = params.draw(&context, actor, RollKind::Hit);
+    // Whether the attack hits depends on the target's dodge roll
+    context.record(RollKind::Hit, value, Outcome::Unknown);
+
+    let line = format!(
+        "{} attacks {}, rolling {value} to hit.",
+        params.name(actor),
+        params.name(target)
+    );
+    params.say(line);
+    Flow::Continue
+}
+
+fn dodge(
+    hit_bonus: u8,
+    mut context: ResMut<ActionContext>,
+    mut params: StepParams,
+    query: Query<(&DodgeChance, Option<&StatusEffects>)>,
+) -> Flow {
+    let (_, target) = match participants(&context, &mut params) {
+        Some(participants) => participants,
+        None => return Flow::Abort,
+    };
+
+    // Without a hit roll, there is nothing to dodge
+    let hit = match context.result(RollKind::Hit) {
+        Some(result) => result.value,
+        None => return Flow::Continue,
+    };
+
+    let value = params.draw(&context, target, RollKind::Dodge);
+
+    // Creatures without a dodge chance can never dodge
+    let dodged = query
+        .get(target)
+        .map_or(false, |(dodge_chance, status_effects)| {
+            let bonus = status_effects.map_or(0, StatusEffects::dodge_bonus);
+            dodge_chance.with_bonus(bonus).dodges(hit, hit_bonus, value)
+        });
+
+    let (outcome, result) = if dodged {
+        (Outcome::Failure, "the attack is dodged")
+    } else {
+        (Outcome::Success, "the attack hits")
+    };
+    context.record(RollKind::Dodge, value, outcome);
+
+    let line = format!("{} rolls {value} to dodge: {result}.", params.name(target));
+    params.say(line);
+
+    if dodged {
+        Flow::Abort
+    } else {
+        Flow::Continue
+    }
+}
+
+fn crit(
+    multiplier: u8,
+    mut context: ResMut<ActionContext>,
+    mut params: StepParams,
+    query: Query<(&CritChance, Option<&StatusEffects>)>,
+    mut life_query: Query<&mut Life>,
+) -> Flow {
+    let actor = match context.actor {
+        Some(actor) => actor,
+        None => return Flow::Abort,
+    };
+
+    let value = params.draw(&context, actor, RollKind::Crit);
+    let crit = query
+        .get(actor)
+        .map_or(false, |(crit_chance, status_effects)| {
+            let bonus = status_effects.map_or(0, StatusEffects::crit_bonus);
+            crit_chance.with_bonus(bonus).roll(value)
+        });
+
+    // Critical hits record their multiplier, so that later damage rolls can use it
+    let (outcome, result) = if crit {
+        (Outcome::Amount(multiplier), "critical hit!")
+    } else {
+        (Outcome::Failure, "no crit.")
+    };
+    context.record(RollKind::Crit, value, outcome);
+
+    let line = format!("{} rolls {value} to crit: {result}", params.name(actor));
+    params.say(line);
+
+    // Damage that was rolled before the crit is topped up to its multiplied amount
+    let dealt = match context
+        .result(RollKind::Damage)
+        .map(|result| result.outcome)
+    {
+        Some(Outcome::Amount(dealt)) if crit => dealt,
+        _ => return Flow::Continue,
+    };
+    let extra = dealt.saturating_mul(multiplier).saturating_sub(dealt);
+
+    for &target in &context.targets {
+        if let Ok(mut life) = life_query.get_mut(target) {
+            *life -= extra;
+
+            let line = format!(
+                "{} takes {extra} more damage from the critical hit, leaving them with {} life.",
+                params.name(target),
+                life.current()
+            );
+            params.say(line);
+        }
+    }
+
+    Flow::Continue
+}
+
+fn damage(
+    range: DamageRange,
+    mut context: ResMut<ActionContext>,
+    mut params: StepParams,
+    mut damage_query: Query<&mut Damage>,
+    mut life_query: Query<&mut Life>,
+) -> Flow {
+    let actor = match participants(&context, &mut params) {
+        Some((actor, _)) => actor,
+        None => return Flow::Abort,
+    };
+
+    let value = params.draw(&context, actor, RollKind::Damage);
+    let rolled = match range {
+        DamageRange::Weapon => damage_query
+            .get_mut(actor)
+            .map_or(0, |mut damage| damage.roll(value)),
+        DamageRange::Fixed { min, max } => Damage::new(min, max).compute(value),
+    };
+
+    let multiplier = match context.result(RollKind::Crit) {
+        Some(result) => crit_multiplier(result.outcome),
+        None => 1,
+    };
+    let dealt = rolled.saturating_mul(multiplier);
+    context.record(RollKind::Damage, value, Outcome::Amount(dealt));
+
+    let line = format!(
+        "{} rolls {value} for damage: {dealt} damage.",
+        params.name(actor)
+    );
+    params.say(line);
+
+    for &target in &context.targets {
+        if let Ok(mut life) = life_query.get_mut(target) {
+            *life -= dealt;
+
+            let line = format!(
+                "{} takes {dealt} damage, leaving them with {} life.",
+                params.name(target),
+                life.current()
+            );
+            params.say(line);
+        }
+    }
+
+    Flow::Continue
+}
+
+fn heal(
+    range: &Damage,
+    mut context: ResMut<ActionContext>,
+    mut params: StepParams,
+    mut life_query: Query<&mut Life>,
+) -> Flow {
+    let actor = match participants(&context, &mut params) {
+        Some((actor, _)) => actor,
+        None => return Flow::Abort,
+    };
+
+    let value = params.draw(&context, actor, RollKind::Heal);
+    let healed = range.compute(value);
+    context.record(RollKind::Heal, value, Outcome::Amount(healed));
+
+    let line = format!(
+        "{} rolls {value} to heal: {healed} life.",
+        params.name(actor)
+    );
+    params.say(line);
+
+    for &target in &context.targets {
+        if let Ok(mut life) = life_query.get_mut(target) {
+            *life += healed;
+
+            let line = format!(
+                "{} is healed for {healed}, and now has {} life.",
+                params.name(target),
+                life.current()
+            );
+            params.say(line);
+        }
+    }
+
+    Flow::Continue
+}
+
+fn lose_life(
+    amount: u8,
+    context: ResMut<ActionContext>,
+    mut params: StepParams,
+    mut life_query: Query<&mut Life>,
+) -> Flow {
+    for &target in &context.targets {
+        if let Ok(mut life) = life_query.get_mut(target) {
+            *life -= amount;
+
+            let line = format!(
+                "{} loses {amount} life, leaving them with {} life.",
+                params.name(target),
+                life.current()
+            );
+            params.say(line);
+        }
+    }
+
+    Flow::Continue
+}
+
+fn gain_life(
+    amount: u8,
+    context: ResMut<ActionContext>,
+    mut params: StepParams,
+    mut life_query: Query<&mut Life>,
+) -> Flow {
+    for &target in &context.targets {
+        if let Ok(mut life) = life_query.get_mut(target) {
+            *life += amount;
+
+            let line = format!(
+                "{} regains {amount} life, and now has {} life.",
+                params.name(target),
+                life.current()
+            );
+            params.say(line);
+        }
+    }
+
+    Flow::Continue
+}
+
+fn kill(
+    context: ResMut<ActionContext>,
+    mut params: StepParams,
+    mut life_query: Query<&mut Life>,
+) -> Flow {
+    for &target in &context.targets {
+        if let Ok(mut life) = life_query.get_mut(target) {
+            life.set_current(0);
+
+            let line = format!("{} is slain.", params.name(target));
+            params.say(line);
+        }
+    }
+
+    Flow::Continue
+}
+
+fn drain_mana(
+    range: &Damage,
+    mut context: ResMut<ActionContext>,
+    mut params: StepParams,
+    mut mana_query: Query<&mut Mana>,
+) -> Flow {
+    let actor = match participants(&context, &mut params) {
+        Some((actor, _)) => actor,
+        None => return Flow::Abort,
+    };
+
+    let value = params.draw(&context, actor, RollKind::Mana);
+    let drained = range.compute(value);
+    context.record(RollKind::Mana, value, Outcome::Amount(drained));
+
+    let line = format!(
+        "{} rolls {value} to drain mana: {drained} mana.",
+        params.name(actor)
+    );
+    params.say(line);
+
+    for &target in &context.targets {
+        if let Ok(mut mana) = mana_query.get_mut(target) {
+            *mana -= drained;
+
+            let line = format!(
+                "{} loses {drained} mana, leaving them with {} mana.",
+                params.name(target),
+                mana.current()
+            );
+            params.say(line);
+        }
+    }
+
+    Flow::Continue
+}
+
+/// The lowest RNG value that lands a coin flip on heads
+const HEADS: u8 = 128;
+
+fn flip(
+    damage: u8,
+    mut context: ResMut<ActionContext>,
+    mut params: StepParams,
+    mut life_query: Query<&mut Life>,
+) -> Flow {
+    let actor = match participants(&context, &mut params) {
+        Some((actor, _)) => actor,
+        None => return Flow::Abort,
+    };
+
+    let value = params.draw(&context, actor, RollKind::CoinFlip);
+    let heads = value >= HEADS;
+
+    if !heads {
+        context.record(RollKind::CoinFlip, value, Outcome::Failure);
+        let line = format!(
+            "{} flips a coin, rolling {value}: tails.",
+            params.name(actor)
+        );
+        params.say(line);
+        return Flow::Continue;
+    }
+
+    context.record(RollKind::CoinFlip, value, Outcome::Success);
+    let line = format!(
+        "{} flips a coin, rolling {value}: heads!",
+        params.name(actor)
+    );
+    params.say(line);
+
+    for &target in &context.targets {
+        if let Ok(mut life) = life_query.get_mut(target) {
+            *life -= damage;
+
+            let line = format!(
+                "{} takes {damage} damage, leaving them with {} life.",
+                params.name(target),
+                life.current()
+            );
+            params.say(line);
+        }
+    }
+
+    Flow::Continue
+}
+
+/// The position of the form chosen by a polymorph `value`, out of `count` forms
+fn chosen_form(value: u8, count: usize) -> usize {
+    value as usize * count / 256
+}
+
+fn polymorph(
+    forms: &[Form],
+    mut context: ResMut<ActionContext>,
+    mut params: StepParams,
+    mut query: Query<(
+        &mut Life,
+        Option<&mut AvailableActions>,
+        Option<&mut Spellbook>,
+    )>,
+) -> Flow {
+    let actor = match participants(&context, &mut params) {
+        Some((actor, _)) => actor,
+        None => return Flow::Abort,
+    };
+
+    let value = params.draw(&context, actor, RollKind::Polymorph);
+    let form = match forms.get(chosen_form(value, forms.len())) {
+        Some(form) => form,
+        None => return Flow::Abort,
+    };
+    context.record(RollKind::Polymorph, value, Outcome::Amount(form.life));
+
+    for &target in &context.targets {
+        if let Ok((mut life, available_actions, spellbook)) = query.get_mut(target) {
+            *life = Life::new(form.life);
+            if let Some(mut available_actions) = available_actions {
+                available_actions.clear();
+            }
+            if let Some(mut spellbook) = spellbook {
+                *spellbook = Spellbook::default();
+            }
+
+            let line = format!(
+                "{} rolls {value} to polymorph: {} turns into a {}, with {} life.",
+                params.name(actor),
+                params.name(target),
+                form.name,
+                form.life
+            );
+            params.say(line);
+        }
+    }
+
+    Flow::Continue
+}
+
+fn reveal(
+    context: ResMut<ActionContext>,
+    mut params: StepParams,
+    mut query: Query<&mut Revealed>,
+) -> Flow {
+    for &target in &context.targets {
+        if let Ok(mut revealed) = query.get_mut(target) {
+            for stat in Stat::ALL {
+                revealed.reveal(stat);
+            }
+
+            let line = format!("Every stat of {} is revealed.", params.name(target));
+            params.say(line);
+        }
+    }
+
+    Flow::Continue
+}
+
+fn check(
+    threshold: u8,
+    otherwise: Option<&str>,
+    mut context: ResMut<ActionContext>,
+    mut params: StepParams,
+) -> Flow {
+    let actor = match context.actor {
+        Some(actor) => actor,
+        None => return Flow::Abort,
+    };
+
+    let value = params.draw(&context, actor, RollKind::Check);
+    let succeeded = value >= threshold;
+
+    let (outcome, result) = if succeeded {
+        (Outcome::Success, "success")
+    } else {
+        (Outcome::Failure, "failure")
+    };
+    context.record(RollKind::Check, value, outcome);
+
+    let line = format!(
+        "{} rolls {value}, needing at least {threshold}: {result}.",
+        params.name(actor)
+    );
+    params.say(line);
+
+    match otherwise {
+        _ if succeeded => Flow::Continue,
+        Some(label) => Flow::Jump(label.to_string()),
+        None => Flow::Abort,
+    }
+}
+
+fn cast(
+    threshold: u8,
+    mut context: ResMut<ActionContext>,
+    mut params: StepParams,
+    query: Query<&Intelligence>,
+) -> Flow {
+    let actor = match context.actor {
+        Some(actor) => actor,
+        None => return Flow::Abort,
+    };
+
+    // Creatures without intelligence get no help casting spells
+    let intelligence = query.get(actor).copied().unwrap_or(Intelligence(0));
+    let needed = SpellSuccess::new(threshold, intelligence).threshold();
+
+    let value = params.draw(&context, actor, RollKind::SpellFail);
+    let succeeded = value >= needed;
+
+    let line = if succeeded {
+        context.record(RollKind::SpellFail, value, Outcome::Success);
+        format!(
+            "{} rolls {value} to cast {}, needing at least {needed}: the spell takes hold.",
+            params.name(actor),
+            context.action
+        )
+    } else {
+        context.record(RollKind::SpellFail, value, Outcome::Failure);
+        format!(
+            "{} rolls {value} to cast {}, needing at least {needed}: the spell fizzles!",
+            params.name(actor),
+            context.action
+        )
+    };
+    params.say(line);
+
+    if succeeded {
+        Flow::Continue
+    } else {
+        Flow::Abort
+    }
+}
+
+fn flee(
+    mut context: ResMut<ActionContext>,
+    mut params: StepParams,
+    query: Query<&FleeChance>,
+    mut commands: Commands,
+) -> Flow {
+    let actor = match context.actor {
+        Some(actor) => actor,
+        None => return Flow::Abort,
+    };
+
+    let value = params.draw(&context, actor, RollKind::Flee);
+    // Creatures without a flee chance can never flee
+    let fled = query
+        .get(actor)
+        .map_or(false, |flee_chance| flee_chance.roll(value));
+
+    let (outcome, result) = if fled {
+        (Outcome::Success, "they escape!")
+    } else {
+        (Outcome::Failure, "they cannot get away.")
+    };
+    context.record(RollKind::Flee, value, outcome);
+
+    if fled {
+        commands.entity(actor).insert(Fled);
+    }
+
+    let line = format!("{} rolls {value} to flee: {result}", params.name(actor));
+    params.say(line);
+    Flow::Continue
+}
+
+fn apply_effect(
+    effect: StatusEffect,
+    context: ResMut<ActionContext>,
+    mut params: StepParams,
+    mut query: Query<&mut StatusEffects>,
+) -> Flow {
+    for &target in &context.targets {
+        if let Ok(mut status_effects) = query.get_mut(target) {
+            status_effects.apply(effect);
+
+            let line = format!(
+                "{} gains {} for {} turns.",
+                params.name(target),
+                effect.kind,
+                effect.duration
+            );
+            params.say(line);
+        }
+    }
+
+    Flow::Continue
+}
+
+fn remove_effects(
+    category: EffectCategory,
+    context: ResMut<ActionContext>,
+    mut params: StepParams,
+    mut query: Query<&mut StatusEffects>,
+    mut removed: EventWriter<EffectRemoved>,
+) -> Flow {
+    for &target in &context.targets {
+        let effects = match query.get_mut(target) {
+            Ok(mut status_effects) => status_effects.remove_category(category),
+            Err(_) => continue,
+        };
+
+        if effects.is_empty() {
+            let line = format!(
+                "{} has no {category} effects to remove.",
+                params.name(target)
+            );
+            params.say(line);
+        }
+
+        for effect in effects {
+            let line = format!("{}'s {} is removed.", params.name(target), effect.kind);
+            params.say(line);
+            removed.send(EffectRemoved {
+                creature: target,
+                effect,
+                cause: RemovalCause::Removed,
+            });
+        }
+    }
+
+    Flow::Continue
+}
+
+fn steal_effects(
+    category: EffectCategory,
+    context: ResMut<ActionContext>,
+    mut params: StepParams,
+    mut query: Query<&mut StatusEffects>,
+    mut removed: EventWriter<EffectRemoved>,
+) -> Flow {
+    let actor = match context.actor {
+        Some(actor) => actor,
+        None => return Flow::Abort,
+    };
+
+    // Creatures that cannot be affected by status effects have nowhere to keep what they would take
+    if query.get(actor).is_err() {
+        let line = format!("{} cannot hold any status effects.", params.name(actor));
+        params.say(line);
+        return Flow::Continue;
+    }
+
+    for &target in &context.targets {
+        // Creatures cannot steal from themselves
+        if target == actor {
+            continue;
+        }
+
+        let effects = match query.get_mut(target) {
+            Ok(mut status_effects) => status_effects.remove_category(category),
+            Err(_) => continue,
+        };
+
+        if effects.is_empty() {
+            let line = format!(
+                "{} has no {category} effects to steal.",
+                params.name(target)
+            );
+            params.say(line);
+        }
+
+        for effect in effects {
+            if let Ok(mut status_effects) = query.get_mut(actor) {
+                status_effects.apply(effect);
+            }
+
+            let line = format!(
+                "{} steals {}'s {}.",
+                params.name(actor),
+                params.name(target),
+                effect.kind
+            );
+            params.say(line);
+            removed.send(EffectRemoved {
+                creature: target,
+                effect,
+                cause: RemovalCause::Stolen { by: actor },
+            });
+        }
+    }
+
+    Flow::Continue
+}
+
+fn invert_effects(
+    category: EffectCategory,
+    context: ResMut<ActionContext>,
+    mut params: StepParams,
+    mut query: Query<&mut StatusEffects>,
+    mut removed: EventWriter<EffectRemoved>,
+) -> Flow {
+    for &target in &context.targets {
+        let inverted = match query.get_mut(target) {
+            Ok(mut status_effects) => status_effects.invert_category(category),
+            Err(_) => continue,
+        };
+
+        if inverted.is_empty() {
+            let line = format!(
+                "{} has no {category} effects to invert.",
+                params.name(target)
+            );
+            params.say(line);
+        }
+
+        for (effect, inverse) in inverted {
+            let line = format!(
+                "{}'s {} is inverted into {}.",
+                params.name(target),
+                effect.kind,
+                inverse.kind
+            );
+            params.say(line);
+            removed.send(EffectRemoved {
+                creature: target,
+                effect,
+                cause: RemovalCause::Inverted { into: inverse.kind },
+            });
+        }
+    }
+
+    Flow::Continue
+}
+
+fn extend_effects(
+    category: EffectCategory,
+    turns: u8,
+    context: ResMut<ActionContext>,
+    mut params: StepParams,
+    mut query: Query<&mut StatusEffects>,
+) -> Flow {
+    for &target in &context.targets {
+        let extended = match query.get_mut(target) {
+            Ok(mut status_effects) => status_effects.extend_category(category, turns),
+            Err(_) => continue,
+        };
+
+        if extended.is_empty() {
+            let line = format!(
+                "{} has no {category} effects to extend.",
+                params.name(target)
+            );
+            params.say(line);
+        }
+
+        for effect in extended {
+            let line = format!(
+                "{}'s {} is extended by {turns} turns, to {} turns.",
+                params.name(target),
+                effect.kind,
+                effect.duration
+            );
+            params.say(line);
+        }
+    }
+
+    Flow::Continue
+}
+
+/// The factor that damage is multiplied by, given the outcome of a crit roll
+fn crit_multiplier(outcome: Outcome) -> u8 {
+    match outcome {
+        Outcome::Amount(multiplier) => multiplier,
+        _ => 1,
+    }
+}
+
+/// Predicts what each of the upcoming RNG `values` will do when used by the `steps`
+///
+/// Prediction stops early when a step would stop the action, or when the values run out.
+/// Rolls made with advantage or disadvantage annotate every value that they use.
+pub fn predict_steps(
+    steps: &[Step],
+    context: &PredictionContext,
+    values: &[u8],
+) -> Vec<Annotation> {
+    let mut prediction = StepPrediction {
+        context,
+        values: values.iter(),
+        annotations: Vec::new(),
+        hit: None,
+        damage: None,
+        multiplier: Some(1),
+    };
+
+    prediction.predict(steps);
+    prediction.annotations
+}
+
+/// The running state of [`predict_steps`]
+struct StepPrediction<'a> {
+    context: &'a PredictionContext,
+    values: std::slice::Iter<'a, u8>,
+    annotations: Vec<Annotation>,
+    /// The hit roll, and the positions of its annotations
+    hit: Option<(u8, std::ops::Range<usize>)>,
+    /// The positions of the annotations of the last damage roll, which a later crit can multiply
+    damage: Option<std::ops::Range<usize>>,
+    /// The damage multiplier from critical hits, which is unknown if the actor's crit chance is unknown
+    multiplier: Option<u8>,
+}
+
+impl<'a> StepPrediction<'a> {
+    /// Predicts each of the `steps`, following jumps to their labels, and returning `false` if the action stops
+    fn predict(&mut self, steps: &[Step]) -> bool {
+        let mut index = 0;
+
+        while let Some(step) = steps.get(index) {
+            index = match self.predict_one(step) {
+                Flow::Continue => index + 1,
+                Flow::Jump(label) => {
+                    match steps
+                        .iter()
+                        .position(|step| matches!(step, Step::Label(other) if *other == label))
+                    {
+                        Some(position) => position,
+                        None => return false,
+                    }
+                }
+                Flow::Skip(_) | Flow::Abort => return false,
+            };
+        }
+        true
+    }
+
+    fn predict_one(&mut self, step: &Step) -> Flow {
+        let roll = match step {
+            Step::Repeat { times, steps } => {
+                return if (0..*times).all(|_| self.predict(steps)) {
+                    Flow::Continue
+                } else {
+                    Flow::Abort
+                };
+            }
+            // Fixed changes and status effects are applied without using any RNG values
+            Step::LoseLife(_)
+            | Step::GainLife(_)
+            | Step::Kill
+            | Step::Reveal
+            | Step::Effect { .. }
+            | Step::Remove { .. }
+            | Step::Steal { .. }
+            | Step::Invert { .. }
+            | Step::Extend { .. }
+            | Step::Label(_) => return Flow::Continue,
+            Step::Hit => RollKind::Hit,
+            Step::Dodge { .. } => RollKind::Dodge,
+            Step::Crit { .. } => RollKind::Crit,
+            Step::Damage(_) => RollKind::Damage,
+            Step::Heal { .. } => RollKind::Heal,
+            Step::DrainMana { .. } => RollKind::Mana,
+            Step::Flip { .. } => RollKind::CoinFlip,
+            Step::Polymorph { .. } => RollKind::Polymorph,
+            Step::Check { .. } => RollKind::Check,
+            Step::Cast { .. } => RollKind::SpellFail,
+            Step::Flee => RollKind::Flee,
+        };
+
+        // Dodge rolls are made by the target, and every other roll by the actor
+        let roller = match roll {
+            RollKind::Dodge => &self.context.target,
+            _ => &self.context.actor,
+        };
+        let mode = roller.advantage.mode(roll);
+
+        let values: Vec<u8> = self.values.by_ref().take(mode.draws).copied().collect();
+        if values.len() < mode.draws {
+            return Flow::Abort;
+        }
+        let value = mode.combine(&values, roll);
+        let positions = self.annotations.len()..self.annotations.len() + mode.draws;
+
+        let outcome = match step {
+            Step::Hit => {
+                self.hit = Some((value, positions.clone()));
+                Outcome::Unknown
+            }
+            Step::Dodge { hit_bonus } => {
+                let outcome = match (&self.context.target.dodge_chance, &self.hit) {
+                    (Some(dodge_chance), Some((hit, _))) => {
+                        if dodge_chance.dodges(*hit, *hit_bonus, value) {
+                            Outcome::Failure
+                        } else {
+                            Outcome::Success
+                        }
+                    }
+                    _ => Outcome::Unknown,
+                };
+
+                // The hit roll succeeds exactly when the dodge roll fails
+                if let Some((_, hit_positions)) = &self.hit {
+                    for annotation in &mut self.annotations[hit_positions.clone()] {
+                        annotation.outcome = outcome;
+                    }
+                }
+                outcome
+            }
+            Step::Crit { multiplier } => {
+                let (outcome, multiplier) = match &self.context.actor.crit_chance {
+                    Some(crit_chance) if crit_chance.roll(value) => {
+                        (Outcome::Success, Some(*multiplier))
+                    }
+                    Some(_) => (Outcome::Failure, Some(1)),
+                    None => (Outcome::Unknown, None),
+                };
+                self.multiplier = multiplier;
+
+                // Damage rolled before the crit is multiplied after the fact
+                if let Some(damage_positions) = self.damage.take() {
+                    for annotation in &mut self.annotations[damage_positions] {
+                        annotation.outcome = match (annotation.outcome, multiplier) {
+                            (Outcome::Amount(dealt), Some(multiplier)) => {
+                                Outcome::Amount(dealt.saturating_mul(multiplier))
+                            }
+                            _ => Outcome::Unknown,
+                        };
+                    }
+                }
+                outcome
+            }
+            Step::Damage(range) => {
+                let rolled = match range {
+                    DamageRange::Weapon => self
+                        .context
+                        .actor
+                        .damage
+                        .as_ref()
+                        .map(|damage| damage.compute(value)),
+                    DamageRange::Fixed { min, max } => Some(Damage::new(*min, *max).compute(value)),
+                };
+
+                self.damage = Some(positions.clone());
+                match (rolled, self.multiplier) {
+                    (Some(rolled), Some(multiplier)) => {
+                        Outcome::Amount(rolled.saturating_mul(multiplier))
+                    }
+                    _ => Outcome::Unknown,
+                }
+            }
+            Step::Heal { min, max } => Outcome::Amount(Damage::new(*min, *max).compute(value)),
+            Step::DrainMana { min, max } => Outcome::Amount(Damage::new(*min, *max).compute(value)),
+            Step::Flip { .. } => {
+                if value >= HEADS {
+                    Outcome::Success
+                } else {
+                    Outcome::Failure
+                }
+            }
+            Step::Polymorph { forms } => match forms.get(chosen_form(value, forms.len())) {
+                Some(form) => Outcome::Amount(form.life),
+                None => Outcome::Unknown,
+            },
+            Step::Check { threshold, .. } => {
+                if value >= *threshold {
+                    Outcome::Success
+                } else {
+                    Outcome::Failure
+                }
+            }
+            Step::Cast { threshold } => match self.context.actor.intelligence {
+                Some(intelligence) if SpellSuccess::new(*threshold, intelligence).roll(value) => {
+                    Outcome::Success
+                }
+                Some(_) => Outcome::Failure,
+                None => Outcome::Unknown,
+            },
+            Step::Flee => match &self.context.actor.flee_chance {
+                Some(flee_chance) if flee_chance.roll(value) => Outcome::Success,
+                Some(_) => Outcome::Failure,
+                None => Outcome::Unknown,
+            },
+            _ => {
+                unreachable!("Steps that use no RNG values of their own are predicted separately.")
+            }
+        };
+
+        for position in 0..mode.draws {
+            let part = mode.is_multiple().then(|| RollPart { position, mode });
+            self.annotations.push(Annotation {
+                roll,
+                outcome,
+                part,
+            });
+        }
+
+        // Dodged attacks and failed checks stop the action, unless the check has somewhere to jump to
+        match step {
+            _ if outcome != Outcome::Failure => Flow::Continue,
+            Step::Check {
+                otherwise: Some(label),
+                ..
+            } => Flow::Jump(label.clone()),
+            _ if matches!(
+                roll,
+                RollKind::Dodge | RollKind::SpellFail | RollKind::Check
+            ) =>
+            {
+                Flow::Abort
+            }
+            _ => Flow::Continue,
+        }
+    }
+}
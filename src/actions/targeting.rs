@@ -0,0 +1,228 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+/// Which creatures an [`Action`](super::Action) can be used on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Targeting {
+    /// Only the creature using the action
+    SelfOnly,
+    /// One creature on the opposing side, chosen by the user
+    SingleEnemy,
+    /// Every creature on the opposing side
+    AllEnemies,
+    /// One creature on the user's side, including the user themselves
+    Ally,
+}
+
+impl Default for Targeting {
+    fn default() -> Self {
+        Targeting::SingleEnemy
+    }
+}
+
+impl Display for Targeting {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Targeting::SelfOnly => "self",
+            Targeting::SingleEnemy => "single enemy",
+            Targeting::AllEnemies => "all enemies",
+            Targeting::Ally => "ally",
+        })
+    }
+}
+
+/// A creature in combat that an action could be used on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct Combatant {
+    /// The creature's entity
+    pub(super) entity: Entity,
+    /// Is the creature on the player's side?
+    pub(super) is_player: bool,
+}
+
+/// The reasons that targets could not be selected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum TargetingError {
+    /// The chosen creature cannot be targeted by this kind of action
+    Invalid,
+    /// There are no creatures that can be targeted
+    NoTargets,
+}
+
+impl Targeting {
+    /// Is the action aimed at the opposing side?
+    #[must_use]
+    pub fn targets_enemies(self) -> bool {
+        matches!(self, Targeting::SingleEnemy | Targeting::AllEnemies)
+    }
+
+    /// Selects the creatures that an action will be used on
+    ///
+    /// The `chosen` creature is used if the action targets a single creature, and must be a valid target.
+    /// If nothing is chosen, single enemy actions target the first of the `combatants` on the opposing side,
+    /// while ally actions target the `user`.
+    /// Every creature in `combatants` should be alive.
+    pub(super) fn select(
+        self,
+        user: Combatant,
+        chosen: Option<Entity>,
+        combatants: &[Combatant],
+    ) -> Result<Vec<Entity>, TargetingError> {
+        let mut enemies = combatants
+            .iter()
+            .filter(|combatant| combatant.is_player != user.is_player)
+            .map(|combatant| combatant.entity);
+        let is_ally = |entity: Entity| {
+            entity == user.entity
+                || combatants.iter().any(|combatant| {
+                    combatant.entity == entity && combatant.is_player == user.is_player
+                })
+        };
+
+        match (self, chosen) {
+            (Targeting::SelfOnly, None) => Ok(vec![user.entity]),
+            (Targeting::SelfOnly, Some(chosen)) if chosen == user.entity => Ok(vec![user.entity]),
+            (Targeting::SingleEnemy, None) => enemies
+                .next()
+                .map(|enemy| vec![enemy])
+                .ok_or(TargetingError::NoTargets),
+            (Targeting::SingleEnemy, Some(chosen)) if enemies.any(|enemy| enemy == chosen) => {
+                Ok(vec![chosen])
+            }
+            // Naming one of the enemies is allowed, but every enemy is still targeted
+            (Targeting::AllEnemies, chosen) => {
+                let enemies: Vec<Entity> = enemies.collect();
+
+                if enemies.is_empty() {
+                    Err(TargetingError::NoTargets)
+                } else if chosen.map_or(true, |chosen| enemies.contains(&chosen)) {
+                    Ok(enemies)
+                } else {
+                    Err(TargetingError::Invalid)
+                }
+            }
+            (Targeting::Ally, None) => Ok(vec![user.entity]),
+            (Targeting::Ally, Some(chosen)) if is_ally(chosen) => Ok(vec![chosen]),
+            _ => Err(TargetingError::Invalid),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The player, followed by two monsters
+    fn combatants() -> [Combatant; 3] {
+        [(0, true), (1, false), (2, false)].map(|(index, is_player)| Combatant {
+            entity: Entity::from_raw(index),
+            is_player,
+        })
+    }
+
+    #[test]
+    fn self_actions_only_target_the_user() {
+        let combatants = combatants();
+        let [player, goblin, _] = combatants;
+        let targeting = Targeting::SelfOnly;
+
+        assert_eq!(
+            targeting.select(player, None, &combatants),
+            Ok(vec![player.entity])
+        );
+        assert_eq!(
+            targeting.select(player, Some(player.entity), &combatants),
+            Ok(vec![player.entity])
+        );
+        assert_eq!(
+            targeting.select(player, Some(goblin.entity), &combatants),
+            Err(TargetingError::Invalid)
+        );
+    }
+
+    #[test]
+    fn single_enemy_actions_target_the_chosen_enemy() {
+        let combatants = combatants();
+        let [player, goblin_1, goblin_2] = combatants;
+        let targeting = Targeting::SingleEnemy;
+
+        assert_eq!(
+            targeting.select(player, Some(goblin_2.entity), &combatants),
+            Ok(vec![goblin_2.entity])
+        );
+        assert_eq!(
+            targeting.select(player, Some(player.entity), &combatants),
+            Err(TargetingError::Invalid)
+        );
+        assert_eq!(
+            targeting.select(goblin_1, Some(goblin_2.entity), &combatants),
+            Err(TargetingError::Invalid)
+        );
+    }
+
+    #[test]
+    fn single_enemy_actions_default_to_the_first_enemy() {
+        let combatants = combatants();
+        let [player, goblin_1, goblin_2] = combatants;
+        let targeting = Targeting::SingleEnemy;
+
+        assert_eq!(
+            targeting.select(player, None, &combatants),
+            Ok(vec![goblin_1.entity])
+        );
+        assert_eq!(
+            targeting.select(goblin_2, None, &combatants),
+            Ok(vec![player.entity])
+        );
+        assert_eq!(
+            targeting.select(player, None, &[player]),
+            Err(TargetingError::NoTargets)
+        );
+    }
+
+    #[test]
+    fn area_actions_target_every_enemy() {
+        let combatants = combatants();
+        let [player, goblin_1, goblin_2] = combatants;
+        let targeting = Targeting::AllEnemies;
+        let goblins = vec![goblin_1.entity, goblin_2.entity];
+
+        assert_eq!(
+            targeting.select(player, None, &combatants),
+            Ok(goblins.clone())
+        );
+        assert_eq!(
+            targeting.select(player, Some(goblin_2.entity), &combatants),
+            Ok(goblins)
+        );
+        assert_eq!(
+            targeting.select(player, Some(player.entity), &combatants),
+            Err(TargetingError::Invalid)
+        );
+        assert_eq!(
+            targeting.select(goblin_1, None, &[goblin_1, goblin_2]),
+            Err(TargetingError::NoTargets)
+        );
+    }
+
+    #[test]
+    fn ally_actions_target_the_users_side() {
+        let combatants = combatants();
+        let [player, goblin_1, goblin_2] = combatants;
+        let targeting = Targeting::Ally;
+
+        assert_eq!(
+            targeting.select(goblin_1, None, &combatants),
+            Ok(vec![goblin_1.entity])
+        );
+        assert_eq!(
+            targeting.select(goblin_1, Some(goblin_2.entity), &combatants),
+            Ok(vec![goblin_2.entity])
+        );
+        assert_eq!(
+            targeting.select(goblin_1, Some(player.entity), &combatants),
+            Err(TargetingError::Invalid)
+        );
+    }
+}
@@ -1,83 +1,223 @@
 //! Describes the flow of combat, and the terminal commands that can be issued
 
+use crate::status_effects::tick_status_effects;
 use bevy::prelude::*;
 use bevy_system_graph::SystemGraph;
+use leafwing_terminal::*;
 
 pub use components::*;
+pub use events::*;
 pub use resources::*;
 use systems::*;
 
 /// Controls the flow of combat
+///
+/// Creatures take turns in the order stored in [`TurnOrder`], which is decided by [`Initiative`].
+/// [`StatusEffects`](crate::status_effects::StatusEffects) trigger at the start of each turn, after action points are refilled.
+/// If there is a terminal, the player can end their turn early with the `end` command.
 pub struct CombatFlowPlugin;
 
 impl Plugin for CombatFlowPlugin {
     fn build(&self, app: &mut App) {
-        // The player always goes first
-        app.insert_resource(CurrentTurn::Player)
+        app.init_resource::<TurnOrder>()
+            .init_resource::<Initiative>()
+            .add_event::<TurnStarted>()
             .add_system_set_to_stage(
                 CoreStage::PreUpdate,
                 SystemGraph::new()
-                    .root(end_turn_when_no_ap)
+                    .root(join_turn_order)
+                    .then(remove_defeated)
+                    .then(end_turn_when_no_ap)
                     .then(update_active_creature)
+                    .then(refill_action_points)
+                    .then(tick_status_effects)
                     .graph()
                     .into(),
             )
             // Runs at the end of PreUpdate
             .add_system_to_stage(CoreStage::PreUpdate, advance_action.exclusive_system());
+
+        if app.world.contains_resource::<TerminalConfiguration>() {
+            app.add_terminal_command::<EndTurnCommand, _, _>(end_turn_command);
+        }
     }
 }
 
 mod systems {
-    use super::{Active, CurrentTurn, Inactive};
+    use super::{Active, Fled, Inactive, Initiative, TurnOrder, TurnStarted};
     use crate::actions::Actions;
-    use crate::combat_statistics::ActionPoints;
+    use crate::combat_statistics::{ActionPoints, Agility, Life, Resource};
     use crate::creatures::{Monster, Player};
+    use crate::rng::{get_next_rng_value, DrawLabel, RNGOutputs, Rng, RngLog, RollKind};
     use bevy::prelude::*;
+    use leafwing_terminal::*;
+
+    /// Adds creatures to the [`TurnOrder`] as they are spawned, according to their [`Initiative`]
+    pub(super) fn join_turn_order(
+        initiative: Res<Initiative>,
+        mut turn_order: ResMut<TurnOrder>,
+        new_creature_query: Query<
+            (Entity, Option<&Agility>, Option<&Player>),
+            Or<(Added<Player>, Added<Monster>)>,
+        >,
+        mut rng: ResMut<Rng>,
+        mut rng_outputs: ResMut<RNGOutputs>,
+        mut rng_log: ResMut<RngLog>,
+    ) {
+        let mut joining: Vec<_> = new_creature_query.iter().collect();
+        // The player goes first when initiative is tied
+        joining.sort_by_key(|(entity, _, player)| (player.is_none(), *entity));
+
+        for (entity, agility, _) in joining {
+            let value = match *initiative {
+                Initiative::Agility => agility.map_or(0, |agility| agility.0),
+                Initiative::Rolled => get_next_rng_value(
+                    &mut *rng,
+                    &mut *rng_outputs,
+                    &mut *rng_log,
+                    DrawLabel::new(entity, "Initiative", RollKind::Initiative),
+                ),
+            };
+
+            turn_order.insert(entity, value);
+        }
+    }
+
+    /// Removes defeated, fled and despawned creatures from the [`TurnOrder`]
+    pub(super) fn remove_defeated(
+        mut turn_order: ResMut<TurnOrder>,
+        query: Query<(Option<&Life>, Option<&Fled>)>,
+    ) {
+        let defeated: Vec<Entity> = turn_order
+            .order()
+            .filter(|entity| match query.get(*entity) {
+                Ok((life, fled)) => {
+                    fled.is_some() || life.map_or(false, |life| life.current() == 0)
+                }
+                Err(_) => true,
+            })
+            .collect();
+
+        for entity in defeated {
+            turn_order.remove(entity);
+        }
+    }
 
     pub(super) fn end_turn_when_no_ap(
-        query: Query<&ActionPoints, With<Active>>,
-        mut current_turn: ResMut<CurrentTurn>,
+        actions: Res<Actions>,
+        mut turn_order: ResMut<TurnOrder>,
+        query: Query<&ActionPoints>,
     ) {
-        let action_points = query.single();
+        // Costs are paid when an action starts, so let the creature finish what it is doing
+        if actions.current().is_some() {
+            return;
+        }
 
-        if *action_points == 0 {
-            current_turn.swap();
+        let out_of_ap = turn_order
+            .current()
+            .and_then(|creature| query.get(creature).ok())
+            .map_or(false, |action_points| *action_points == 0);
+
+        if out_of_ap {
+            turn_order.end_turn();
         }
     }
 
+    /// Marks the creature whose turn it is as [`Active`], and every other creature as [`Inactive`]
+    ///
+    /// Sends a [`TurnStarted`] event whenever a new turn begins.
     pub(super) fn update_active_creature(
         mut commands: Commands,
-        current_turn: Res<CurrentTurn>,
-        player_query: Query<Entity, With<Player>>,
-        monster_query: Query<Entity, With<Monster>>,
+        turn_order: Res<TurnOrder>,
+        creature_query: Query<Entity, Or<(With<Player>, With<Monster>)>>,
+        mut turn_started: EventWriter<TurnStarted>,
+        mut last_turn: Local<Option<(Entity, usize)>>,
     ) {
-        if current_turn.is_changed() {
-            let player = player_query.single();
-            let monster = monster_query.single();
-
-            match *current_turn {
-                CurrentTurn::Player => {
-                    commands.entity(player).insert(Active).remove::<Inactive>();
-                    commands.entity(monster).insert(Inactive).remove::<Active>();
-                }
-                CurrentTurn::Monster => {
-                    commands.entity(monster).insert(Active).remove::<Inactive>();
-                    commands.entity(player).insert(Inactive).remove::<Active>();
-                }
+        if !turn_order.is_changed() {
+            return;
+        }
+
+        let current = turn_order.current();
+
+        for entity in creature_query.iter() {
+            if Some(entity) == current {
+                commands.entity(entity).insert(Active).remove::<Inactive>();
+            } else {
+                commands.entity(entity).insert(Inactive).remove::<Active>();
+            }
+        }
+
+        if let Some(creature) = current {
+            // Creatures joining the turn order do not start a new turn
+            if *last_turn != Some((creature, turn_order.turn())) {
+                *last_turn = Some((creature, turn_order.turn()));
+                turn_started.send(TurnStarted {
+                    creature,
+                    round: turn_order.round(),
+                });
+            }
+        }
+    }
+
+    /// Refills the [`ActionPoints`] of each creature when its turn starts
+    pub(super) fn refill_action_points(
+        mut turn_started: EventReader<TurnStarted>,
+        mut query: Query<&mut ActionPoints>,
+    ) {
+        for TurnStarted { creature, .. } in turn_started.iter() {
+            if let Ok(mut action_points) = query.get_mut(*creature) {
+                let max = action_points.max();
+                action_points.set_current(max);
             }
         }
     }
 
+    /// Ends the player's turn, even if they have action points left
+    #[derive(TerminalCommand)]
+    #[terminal_command(name = "end")]
+    pub(super) struct EndTurnCommand;
+
+    pub(super) fn end_turn_command(
+        mut terminal_command: TerminalCommand<EndTurnCommand>,
+        actions: Res<Actions>,
+        player_query: Query<(), (With<Player>, With<Active>)>,
+        mut turn_order: ResMut<TurnOrder>,
+    ) {
+        if terminal_command.take().is_none() {
+            return;
+        }
+
+        if player_query.iter().next().is_none() {
+            terminal_command.reply_failed("It is not your turn.");
+        } else if actions.current().is_some() {
+            terminal_command.reply_failed("You cannot end your turn while an action is queued.");
+        } else {
+            turn_order.end_turn();
+            terminal_command.reply_ok("You end your turn.");
+        }
+    }
+
     /// Runs the next system in the [`Action`] on the [`World`] when any keyboard button is pressed
     pub(super) fn advance_action(world: &mut World) {
         // Is an action active?
         world.resource_scope(|world, mut actions: Mut<Actions>| {
-            if let Some(action_name) = actions.current() {
-                let keyboard_input: &Input<KeyCode> = world.get_resource().unwrap();
+            if let Some(action_id) = actions.current() {
+                let advance_pressed = world
+                    .get_resource::<Input<KeyCode>>()
+                    .map_or(false, |keyboard_input| {
+                        keyboard_input.just_pressed(KeyCode::Return)
+                    });
 
-                if keyboard_input.just_pressed(KeyCode::Return) {
+                if advance_pressed {
                     // Run the next system in the action on the world
-                    let action = actions.get_mut(action_name);
+                    let action = match actions.get_mut(&action_id) {
+                        Ok(action) => action,
+                        Err(error) => {
+                            warn!("Could not advance the queued action: {error}");
+                            actions.clear();
+                            return;
+                        }
+                    };
                     action.advance(world);
 
                     // Reset the state of the `Action` if it's complete
@@ -95,38 +235,256 @@ mod systems {
 }
 
 mod resources {
-    /// Whose turn is it?
-    #[allow(missing_docs)]
-    #[derive(PartialEq, Clone, Copy, Debug)]
-    pub enum CurrentTurn {
-        Player,
-        Monster,
-    }
-
-    impl CurrentTurn {
-        /// Changes the turn to the opposite variant
-        pub fn swap(&mut self) {
-            use CurrentTurn::*;
-            *self = match self {
-                Player => Monster,
-                Monster => Player,
+    use bevy::prelude::*;
+
+    /// The order in which creatures take their turns, stored as a resource
+    ///
+    /// Creatures are sorted by their initiative, highest first, and take one turn each per round.
+    #[derive(Debug, Clone, Default, PartialEq, Eq)]
+    pub struct TurnOrder {
+        entries: Vec<(Entity, u8)>,
+        index: usize,
+        round: usize,
+        turn: usize,
+    }
+
+    impl TurnOrder {
+        /// The creature whose turn it is, if any
+        #[must_use]
+        pub fn current(&self) -> Option<Entity> {
+            self.entries.get(self.index).map(|(entity, _)| *entity)
+        }
+
+        /// Every creature in combat, in the order that they take their turns
+        pub fn order(&self) -> impl Iterator<Item = Entity> + '_ {
+            self.entries.iter().map(|(entity, _)| *entity)
+        }
+
+        /// The initiative of the `creature`, if it is in combat
+        #[must_use]
+        pub fn initiative(&self, creature: Entity) -> Option<u8> {
+            self.entries
+                .iter()
+                .find(|(entity, _)| *entity == creature)
+                .map(|(_, initiative)| *initiative)
+        }
+
+        /// The number of rounds that have been completed
+        #[must_use]
+        pub fn round(&self) -> usize {
+            self.round
+        }
+
+        /// The number of turns that have been taken
+        #[must_use]
+        pub fn turn(&self) -> usize {
+            self.turn
+        }
+
+        /// Adds the `creature` to the turn order, after any creatures with at least as much `initiative`
+        ///
+        /// The creature whose turn it is keeps their turn.
+        pub fn insert(&mut self, creature: Entity, initiative: u8) {
+            let position = self
+                .entries
+                .iter()
+                .position(|(_, other)| *other < initiative)
+                .unwrap_or(self.entries.len());
+
+            if !self.entries.is_empty() && position <= self.index {
+                self.index += 1;
             }
+            self.entries.insert(position, (creature, initiative));
+        }
+
+        /// Removes the `creature` from the turn order, returning whether it was present
+        ///
+        /// If it was their turn, the next creature's turn starts.
+        pub fn remove(&mut self, creature: Entity) -> bool {
+            let position = match self
+                .entries
+                .iter()
+                .position(|(entity, _)| *entity == creature)
+            {
+                Some(position) => position,
+                None => return false,
+            };
+            self.entries.remove(position);
+
+            if position < self.index {
+                self.index -= 1;
+            } else if position == self.index {
+                self.turn += 1;
+                self.wrap();
+            }
+            true
+        }
+
+        /// Ends the current creature's turn, and starts the next creature's
+        pub fn end_turn(&mut self) {
+            if self.entries.is_empty() {
+                return;
+            }
+
+            self.index += 1;
+            self.turn += 1;
+            self.wrap();
+        }
+
+        /// Starts a new round once every creature has taken their turn
+        fn wrap(&mut self) {
+            if self.index >= self.entries.len() {
+                self.index = 0;
+                self.round += 1;
+            }
+        }
+    }
+
+    /// How the [`TurnOrder`] is decided when creatures join combat, stored as a resource
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Initiative {
+        /// Creatures with more [`Agility`](crate::combat_statistics::Agility) act first
+        ///
+        /// Ties go to the player, then to whoever joined combat first.
+        Agility,
+        /// Each creature draws an RNG value when it joins combat, and higher values act first
+        Rolled,
+    }
+
+    impl Default for Initiative {
+        fn default() -> Self {
+            Initiative::Agility
         }
     }
 }
 
+mod events {
+    use bevy::prelude::*;
+
+    /// Sent when a creature's turn starts
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct TurnStarted {
+        /// The creature whose turn it is
+        pub creature: Entity,
+        /// The number of rounds completed before this turn
+        pub round: usize,
+    }
+}
+
 mod components {
     use bevy::prelude::*;
 
-    #[derive(Component, Debug)]
+    #[derive(Component, Clone, Copy, Debug)]
     /// An entity whose turn it is
     ///
-    /// The counterpart to [`Inactive`], controlled by [`CurrentTurn`].
+    /// The counterpart to [`Inactive`], controlled by the [`TurnOrder`](super::TurnOrder).
     pub struct Active;
 
-    #[derive(Component, Debug)]
-    /// An entity whose turn it is
+    #[derive(Component, Clone, Copy, Debug)]
+    /// An entity whose turn it is not
     ///
-    /// The counterpart to [`Inactive`], controlled by [`CurrentTurn`].
+    /// The counterpart to [`Active`], controlled by the [`TurnOrder`](super::TurnOrder).
     pub struct Inactive;
+
+    #[derive(Component, Clone, Copy, Debug)]
+    /// A creature that has escaped from combat, and no longer takes turns
+    pub struct Fled;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combat_statistics::{Life, Resource};
+
+    /// A turn order of three creatures, returned in the order that they act
+    fn turn_order() -> (TurnOrder, [Entity; 3]) {
+        let [first, second, third] = [0, 1, 2].map(Entity::from_raw);
+        let mut turn_order = TurnOrder::default();
+        turn_order.insert(first, 5);
+        // Ties go to whoever joined first
+        turn_order.insert(second, 3);
+        turn_order.insert(third, 3);
+
+        (turn_order, [first, second, third])
+    }
+
+    #[test]
+    fn creatures_act_in_order_of_initiative() {
+        let (mut turn_order, [first, second, third]) = turn_order();
+        assert_eq!(turn_order.current(), Some(first));
+        turn_order.end_turn();
+
+        let quick = Entity::from_raw(3);
+        turn_order.insert(quick, 9);
+
+        assert!(turn_order.order().eq([quick, first, second, third]));
+        assert_eq!(turn_order.current(), Some(second));
+    }
+
+    #[test]
+    fn rounds_wrap_after_every_creature_acts() {
+        let (mut turn_order, [first, second, _]) = turn_order();
+
+        for _ in 0..3 {
+            assert_eq!(turn_order.round(), 0);
+            turn_order.end_turn();
+        }
+
+        assert_eq!(turn_order.round(), 1);
+        assert_eq!(turn_order.turn(), 3);
+        assert_eq!(turn_order.current(), Some(first));
+
+        turn_order.end_turn();
+        assert_eq!(turn_order.current(), Some(second));
+    }
+
+    #[test]
+    fn removing_an_earlier_creature_keeps_the_current_turn() {
+        let (mut turn_order, [first, second, _]) = turn_order();
+        turn_order.end_turn();
+
+        assert!(turn_order.remove(first));
+        assert_eq!(turn_order.current(), Some(second));
+        assert_eq!(turn_order.turn(), 1);
+        assert!(!turn_order.remove(first));
+    }
+
+    #[test]
+    fn removing_the_current_creature_starts_the_next_turn() {
+        let (mut turn_order, [first, second, third]) = turn_order();
+        turn_order.end_turn();
+
+        turn_order.remove(second);
+        assert_eq!(turn_order.current(), Some(third));
+        assert_eq!(turn_order.turn(), 2);
+
+        // Removing the last creature in the round starts the next round
+        turn_order.remove(third);
+        assert_eq!(turn_order.current(), Some(first));
+        assert_eq!(turn_order.round(), 1);
+    }
+
+    #[test]
+    fn defeated_creatures_are_removed() {
+        let mut world = World::new();
+        let alive = world.spawn().insert(Life::new(5)).id();
+        let mut life = Life::new(5);
+        life.set_current(0);
+        let defeated = world.spawn().insert(life).id();
+        let fled = world.spawn().insert(Life::new(5)).insert(Fled).id();
+        let despawned = world.spawn().id();
+        world.despawn(despawned);
+
+        let mut turn_order = TurnOrder::default();
+        for creature in [defeated, alive, fled, despawned] {
+            turn_order.insert(creature, 0);
+        }
+        world.insert_resource(turn_order);
+
+        SystemStage::single(remove_defeated).run(&mut world);
+
+        let turn_order = world.get_resource::<TurnOrder>().unwrap();
+        assert!(turn_order.order().eq([alive]));
+        assert_eq!(turn_order.current(), Some(alive));
+    }
 }
@@ -1,35 +1,326 @@
 //! Transition in and out of combat
 
-use crate::combat_statistics::{Damage, Life, Resource};
-use crate::creatures::{Monster, Player};
+use crate::actions::{ActionId, Actions, AvailableActions};
+use crate::combat_flow::{Fled, TurnOrder};
+use crate::combat_statistics::*;
+use crate::creatures::{Monster, MonsterBundle, Player, PlayerBundle};
+use crate::monster_ai::{Blocks, MonsterAi};
+use crate::prediction::Revealed;
+use crate::rng::Advantage;
+use crate::spells::Spellbook;
+use crate::status_effects::StatusEffects;
 use crate::GameState;
 use bevy::prelude::*;
+use leafwing_terminal::*;
+use std::fmt::Display;
 
 /// Adds, removes and cleans up entities when combat starts and ends
+///
+/// Combat starts when a [`StartCombat`] event is sent, or the `fight` command is entered,
+/// and ends when either side is defeated or the player flees, sending a [`CombatResult`] event.
 pub struct CombatSetupPlugin;
 
 impl Plugin for CombatSetupPlugin {
     fn build(&self, app: &mut App) {
-        app.add_state(GameState::GameStart).add_system_set(
-            SystemSet::on_enter(GameState::GameStart)
-                .with_system(spawn_player)
-                .with_system(spawn_enemy),
-        );
+        app.add_state(GameState::GameStart)
+            .add_event::<StartCombat>()
+            .add_event::<CombatResult>()
+            .add_system_set(SystemSet::on_update(GameState::GameStart).with_system(start_combat))
+            .add_system_set(SystemSet::on_update(GameState::OutOfCombat).with_system(start_combat))
+            .add_system_set(
+                SystemSet::on_enter(GameState::InCombat)
+                    .with_system(spawn_player)
+                    .with_system(spawn_monsters),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::InCombat).with_system(detect_combat_end),
+            )
+            .add_system_set(SystemSet::on_exit(GameState::InCombat).with_system(clean_up_combat));
+
+        if app.world.contains_resource::<TerminalConfiguration>() {
+            app.add_terminal_command::<FightCommand, _, _>(fight_command)
+                .add_system_set(
+                    SystemSet::on_enter(GameState::GameStart).with_system(print_fight_hint),
+                )
+                .add_system_set(
+                    SystemSet::on_enter(GameState::OutOfCombat).with_system(print_fight_hint),
+                );
+        }
+    }
+}
+
+/// Starts a fight, if one is not already underway
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StartCombat;
+
+/// How a fight ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CombatOutcome {
+    /// Every monster was defeated
+    Victory,
+    /// The player was defeated
+    Defeat,
+    /// The player escaped
+    Fled,
+}
+
+/// Sent when a fight ends, summarizing what happened
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CombatResult {
+    /// How the fight ended
+    pub outcome: CombatOutcome,
+    /// The number of rounds that the fight lasted, including the last one
+    pub rounds: usize,
+    /// The number of turns taken by every creature
+    pub turns: usize,
+    /// The names of the monsters that were defeated
+    pub defeated: Vec<String>,
+    /// The life that the player had left
+    pub player_life: u8,
+}
+
+impl Display for CombatResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let rounds = match self.rounds {
+            1 => "1 round".to_string(),
+            rounds => format!("{rounds} rounds"),
+        };
+        let turns = match self.turns {
+            1 => "1 turn".to_string(),
+            turns => format!("{turns} turns"),
+        };
+        let ending = match self.outcome {
+            CombatOutcome::Victory => "Victory!",
+            CombatOutcome::Defeat => "Defeat...",
+            CombatOutcome::Fled => "You got away.",
+        };
+        let defeated = match self.defeated.len() {
+            0 => "no monsters".to_string(),
+            _ => self.defeated.join(", "),
+        };
+
+        f.write_fmt(format_args!(
+            "{ending} The fight lasted {rounds} and {turns}, in which you defeated {defeated}."
+        ))
     }
 }
 
+/// Starts a fight
+#[derive(TerminalCommand)]
+#[terminal_command(name = "fight")]
+struct FightCommand;
+
+fn fight_command(
+    mut terminal_command: TerminalCommand<FightCommand>,
+    game_state: Res<State<GameState>>,
+    mut start_events: EventWriter<StartCombat>,
+) {
+    if terminal_command.take().is_none() {
+        return;
+    }
+
+    if *game_state.current() == GameState::InCombat {
+        terminal_command.reply_failed("You are already in a fight.");
+    } else {
+        start_events.send(StartCombat);
+        terminal_command.reply_ok("A fight begins!");
+    }
+}
+
+fn print_fight_hint(mut lines: EventWriter<PrintTerminalLine>) {
+    lines.send(PrintTerminalLine::new(
+        "Enter `fight` to start a fight.".to_string(),
+    ));
+}
+
+fn start_combat(
+    mut start_events: EventReader<StartCombat>,
+    mut game_state: ResMut<State<GameState>>,
+) {
+    if start_events.iter().next().is_some() {
+        if let Err(error) = game_state.set(GameState::InCombat) {
+            warn!("Could not start combat: {error:?}");
+        }
+    }
+}
+
+/// Ends the fight once the player or every monster is defeated, or the player flees
+///
+/// Waits for the queued action to finish first, so that it is never interrupted.
+fn detect_combat_end(
+    actions: Res<Actions>,
+    turn_order: Res<TurnOrder>,
+    player_query: Query<(&Life, Option<&Fled>), With<Player>>,
+    monster_query: Query<(&Life, Option<&Fled>, Option<&Name>), With<Monster>>,
+    mut game_state: ResMut<State<GameState>>,
+    mut results: EventWriter<CombatResult>,
+    mut lines: EventWriter<PrintTerminalLine>,
+) {
+    if actions.current().is_some() {
+        return;
+    }
+
+    // The creatures are spawned at the end of the first frame of combat
+    let (player_life, player_fled) = match player_query.get_single() {
+        Ok((life, fled)) => (life.current(), fled.is_some()),
+        Err(_) => return,
+    };
+    let monsters_remain = monster_query
+        .iter()
+        .any(|(life, fled, _)| life.current() > 0 && fled.is_none());
+
+    let outcome = if player_life == 0 {
+        CombatOutcome::Defeat
+    } else if player_fled {
+        CombatOutcome::Fled
+    } else if !monsters_remain {
+        CombatOutcome::Victory
+    } else {
+        return;
+    };
+
+    let defeated = monster_query
+        .iter()
+        .filter(|(life, ..)| life.current() == 0)
+        .map(|(.., name)| name.map_or("a monster", |name| name.as_str()).to_string())
+        .collect();
+
+    let result = CombatResult {
+        outcome,
+        rounds: turn_order.round() + 1,
+        turns: turn_order.turn() + 1,
+        defeated,
+        player_life,
+    };
+    lines.send(PrintTerminalLine::new(result.to_string()));
+    results.send(result);
+
+    if let Err(error) = game_state.set(GameState::OutOfCombat) {
+        warn!("Could not end combat: {error:?}");
+    }
+}
+
+/// Despawns every creature that took part in the fight, and resets the turn order
+fn clean_up_combat(
+    mut commands: Commands,
+    creature_query: Query<Entity, Or<(With<Player>, With<Monster>)>>,
+    mut turn_order: ResMut<TurnOrder>,
+    mut actions: ResMut<Actions>,
+) {
+    for entity in creature_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    *turn_order = TurnOrder::default();
+    actions.clear();
+}
+
+/// The actions that every creature starts combat with
+fn starting_actions() -> AvailableActions {
+    let mut actions = AvailableActions::default();
+    actions.insert(ActionId::Attack);
+    actions
+}
+
 fn spawn_player(mut commands: Commands) {
+    let agility = Agility(4);
+    let intelligence = Intelligence(5);
+
     commands
-        .spawn()
-        .insert(Life::new(10))
-        .insert(Damage::new(3, 5))
-        .insert(Player);
+        .spawn_bundle(PlayerBundle {
+            player: Player,
+            life: Life::new(10),
+            mana: Mana::compute(50, intelligence),
+            ap: ActionPoints::new(3),
+            actions: player_actions(),
+            spellbook: player_spells(),
+            agility,
+            intelligence,
+            damage: Damage::new(3, 5),
+            crit_chance: CritChance::new(agility),
+            dodge_chance: DodgeChance::new(agility),
+            flee_chance: FleeChance::new(agility),
+            status_effects: StatusEffects::default(),
+            advantage: Advantage::default(),
+        })
+        .insert(Name::new("Player"));
 }
 
-fn spawn_enemy(mut commands: Commands) {
-    commands
-        .spawn()
-        .insert(Life::new(10))
-        .insert(Damage::new(6, 9))
-        .insert(Monster);
+/// The actions that the player starts combat with
+fn player_actions() -> AvailableActions {
+    let mut actions = starting_actions();
+    actions.insert(ActionId::Flee);
+    actions
+}
+
+/// The spells that the player starts combat with
+fn player_spells() -> Spellbook {
+    let mut spellbook = Spellbook::default();
+    spellbook.insert(ActionId::from_name("Fire Bolt"));
+    spellbook
+}
+
+/// The names of the monsters fought in each encounter, which are used to target them
+const MONSTER_NAMES: [&str; 2] = ["Goblin 1", "Goblin 2"];
+
+fn spawn_monsters(mut commands: Commands) {
+    let agility = Agility(2);
+    let intelligence = Intelligence(0);
+
+    for name in MONSTER_NAMES {
+        commands
+            .spawn_bundle(MonsterBundle {
+                monster: Monster,
+                life: Life::new(6),
+                mana: Mana::compute(0, intelligence),
+                ap: ActionPoints::new(2),
+                actions: starting_actions(),
+                spellbook: Spellbook::default(),
+                agility,
+                intelligence,
+                damage: Damage::new(2, 4),
+                crit_chance: CritChance::new(agility),
+                dodge_chance: DodgeChance::new(agility),
+                flee_chance: FleeChance::new(agility),
+                status_effects: StatusEffects::default(),
+                advantage: Advantage::default(),
+                revealed: Revealed::default(),
+            })
+            .insert(Name::new(name))
+            .insert(MonsterAi::new(
+                Blocks::new().with_block(0..=255, ActionId::Attack),
+            ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn results_describe_the_fight() {
+        let mut result = CombatResult {
+            outcome: CombatOutcome::Victory,
+            rounds: 1,
+            turns: 1,
+            defeated: vec!["Goblin 1".to_string()],
+            player_life: 10,
+        };
+        assert_eq!(
+            result.to_string(),
+            "Victory! The fight lasted 1 round and 1 turn, in which you defeated Goblin 1."
+        );
+
+        result = CombatResult {
+            outcome: CombatOutcome::Defeat,
+            rounds: 3,
+            turns: 8,
+            defeated: Vec::new(),
+            player_life: 0,
+        };
+        assert_eq!(
+            result.to_string(),
+            "Defeat... The fight lasted 3 rounds and 8 turns, in which you defeated no monsters."
+        );
+    }
 }
@@ -45,6 +45,33 @@ mod resources {
         fn set_max(&mut self, max: u8);
     }
 
+    /// The current values of the [`Resource`] components of a creature
+    ///
+    /// Components that the creature does not have are `None`.
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+    #[allow(missing_docs)]
+    pub struct CreatureResources {
+        pub life: Option<u8>,
+        pub mana: Option<u8>,
+        pub action_points: Option<u8>,
+    }
+
+    impl CreatureResources {
+        /// Reads the current values of a creature's resource components
+        #[must_use]
+        pub fn new(
+            life: Option<&Life>,
+            mana: Option<&Mana>,
+            action_points: Option<&ActionPoints>,
+        ) -> Self {
+            CreatureResources {
+                life: life.map(Resource::current),
+                mana: mana.map(Resource::current),
+                action_points: action_points.map(Resource::current),
+            }
+        }
+    }
+
     /// The life points of a creature
     #[derive(Component, Clone, Debug, PartialEq, PartialOrd)]
     pub struct Life {
@@ -55,10 +82,10 @@ mod resources {
     impl Life {
         /// Computes the life total of a creature based on their [`Strength`]
         ///
-        /// The `base` for the player is 40.
+        /// The `base` for the player is 40. Totals beyond the largest `u8` are capped.
         #[must_use]
         fn compute(base: u8, strength: Strength) -> Self {
-            Life::new(base + 4 * strength.0)
+            Life::new(base.saturating_add(strength.0.saturating_mul(4)))
         }
     }
 
@@ -76,18 +103,14 @@ mod resources {
         }
 
         fn set_current(&mut self, current: u8) {
-            if self.max <= current {
-                self.current = current;
-            } else {
-                self.current = self.max;
-            }
+            self.current = current.min(self.max);
         }
 
         fn set_max(&mut self, max: u8) {
             self.max = max;
 
-            if max > self.current {
-                self.current = self.max;
+            if self.current > max {
+                self.current = max;
             }
         }
     }
@@ -100,12 +123,12 @@ mod resources {
     }
 
     impl Mana {
-        /// Computes the mana total of a creature based on their [`Intelligence`](super::Strength)
+        /// Computes the mana total of a creature based on their [`Intelligence`](super::Intelligence)
         ///
-        /// The `base` for the player is 50.
+        /// The `base` for the player is 50. Totals beyond the largest `u8` are capped.
         #[must_use]
-        fn compute(base: u8, intelligence: Intelligence) -> Self {
-            Mana::new(base + intelligence.0)
+        pub fn compute(base: u8, intelligence: Intelligence) -> Self {
+            Mana::new(base.saturating_add(intelligence.0))
         }
     }
 
@@ -123,18 +146,14 @@ mod resources {
         }
 
         fn set_current(&mut self, current: u8) {
-            if self.max <= current {
-                self.current = current;
-            } else {
-                self.current = self.max;
-            }
+            self.current = current.min(self.max);
         }
 
         fn set_max(&mut self, max: u8) {
             self.max = max;
 
-            if max > self.current {
-                self.current = self.max;
+            if self.current > max {
+                self.current = max;
             }
         }
     }
@@ -160,18 +179,14 @@ mod resources {
         }
 
         fn set_current(&mut self, current: u8) {
-            if self.max <= current {
-                self.current = current;
-            } else {
-                self.current = self.max;
-            }
+            self.current = current.min(self.max);
         }
 
         fn set_max(&mut self, max: u8) {
             self.max = max;
 
-            if max > self.current {
-                self.current = self.max;
+            if self.current > max {
+                self.current = max;
             }
         }
     }
@@ -258,7 +273,7 @@ mod resource_impls {
         fn sub(self, rhs: u8) -> Self::Output {
             let mut new = self.clone();
 
-            if let Some(new_value) = self.current().checked_add(rhs) {
+            if let Some(new_value) = self.current().checked_sub(rhs) {
                 new.set_current(new_value);
             } else {
                 new.set_current(u8::MIN);
@@ -383,11 +398,17 @@ mod damage {
 
         /// Determine how much damage is dealt based on a provided `rng`
         pub fn roll(&mut self, rng: u8) -> u8 {
+            let damage = self.compute(rng);
+            self.actual = Some(damage);
+            damage
+        }
+
+        /// Computes how much damage would be dealt by a provided `rng`, without storing it
+        pub fn compute(&self, rng: u8) -> u8 {
             let fraction: f32 = rng as f32 / 255.;
             let range = self.max - self.min;
 
             let damage_f32 = self.min as f32 + fraction * range as f32;
-            self.actual = Some(damage_f32 as u8);
             damage_f32 as u8
         }
 
@@ -519,13 +540,19 @@ mod derived_stats {
         pub fn new(agility: Agility) -> Self {
             let fraction: f32 = (Self::BASE + Self::SCALING * agility.0 as f32).clamp(0., 1.);
 
-            CritChance(255 * fraction as u8)
+            CritChance((255. * fraction) as u8)
         }
 
         /// Given a provided `rng` input, is the attack a crit?
         pub fn roll(&self, rng: u8) -> bool {
             self.0 >= rng
         }
+
+        /// This crit chance, increased by a temporary `bonus`
+        #[must_use]
+        pub fn with_bonus(&self, bonus: u8) -> Self {
+            CritChance(self.0.saturating_add(bonus))
+        }
     }
 
     /// The chance to dodge an attack
@@ -543,13 +570,26 @@ mod derived_stats {
         pub fn new(agility: Agility) -> Self {
             let fraction: f32 = (Self::BASE + Self::SCALING * agility.0 as f32).clamp(0., 1.);
 
-            DodgeChance(255 * fraction as u8)
+            DodgeChance((255. * fraction) as u8)
         }
 
         /// Given a provided `rng` input, is the attack dodged?
         pub fn roll(&self, rng: u8) -> bool {
             self.0 >= rng
         }
+
+        /// This dodge chance, increased by a temporary `bonus`
+        #[must_use]
+        pub fn with_bonus(&self, bonus: u8) -> Self {
+            DodgeChance(self.0.saturating_add(bonus))
+        }
+
+        /// Resolves an opposed roll between an attacker's `hit` roll and this creature's `dodge` roll
+        ///
+        /// The attack is dodged if the dodge roll plus this dodge chance beats the hit roll plus the attacker's `hit_bonus`.
+        pub fn dodges(&self, hit: u8, hit_bonus: u8, dodge: u8) -> bool {
+            dodge as u16 + self.0 as u16 > hit as u16 + hit_bonus as u16
+        }
     }
 
     /// The chance to flee from combat
@@ -567,7 +607,7 @@ mod derived_stats {
         pub fn new(agility: Agility) -> Self {
             let fraction: f32 = (Self::BASE + Self::SCALING * agility.0 as f32).clamp(0., 1.);
 
-            FleeChance(255 * fraction as u8)
+            FleeChance((255. * fraction) as u8)
         }
 
         /// Given a provided `rng` input, is the attack dodged?
@@ -576,24 +616,90 @@ mod derived_stats {
         }
     }
 
-    /// The chance that a particular spell succeeds
+    /// The lowest roll that successfully casts a particular spell
+    ///
+    /// Each spell has a base fail threshold, which is lowered by the caster's [`Intelligence`].
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
     pub struct SpellSuccess(u8);
 
     impl SpellSuccess {
-        /// The marginal fraction of  spells that will succeed for each point of intelligence gained
+        /// The marginal fraction of spells that will succeed for each point of intelligence gained
         const SCALING: f32 = 1. / 100.;
 
-        /// Creates a new [`Flee`] component
-        pub fn new(base_chance: u8, intelligence: Intelligence) -> Self {
-            let fraction: f32 =
-                (base_chance as f32 + Self::SCALING * intelligence.0 as f32).clamp(0., 1.);
+        /// Creates a new [`SpellSuccess`], given the spell's base `fail_threshold` and the caster's `intelligence`
+        pub fn new(fail_threshold: u8, intelligence: Intelligence) -> Self {
+            let lowered_by = 255. * Self::SCALING * intelligence.0 as f32;
 
-            SpellSuccess(255 * fraction as u8)
+            SpellSuccess((fail_threshold as f32 - lowered_by).clamp(0., 255.) as u8)
+        }
+
+        /// The lowest roll that casts the spell
+        pub fn threshold(&self) -> u8 {
+            self.0
         }
 
         /// Given a provided `rng` input, does the spell succeed?
         pub fn roll(&self, rng: u8) -> bool {
-            self.0 >= rng
+            rng >= self.0
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subtracting_lowers_resources_down_to_zero() {
+        let life = Life::new(10) - 3;
+        assert_eq!(life.current(), 7);
+
+        let mana = Mana::new(10) - 20;
+        assert_eq!(mana.current(), 0);
+
+        let mut action_points = ActionPoints::new(10);
+        action_points -= 4;
+        assert_eq!(action_points.current(), 6);
+    }
+
+    #[test]
+    fn current_values_are_capped_at_the_max() {
+        let mut life = Life::new(10);
+        life.set_current(5);
+        assert_eq!(life.current(), 5);
+
+        life.set_current(20);
+        assert_eq!(life.current(), 10);
+    }
+
+    #[test]
+    fn changing_the_max_only_lowers_the_current_value() {
+        let mut mana = Mana::new(10);
+        mana.set_current(5);
+
+        mana.set_max(20);
+        assert_eq!(mana.current(), 5);
+
+        mana.set_max(3);
+        assert_eq!(mana.current(), 3);
+    }
+
+    #[test]
+    fn computed_totals_are_capped() {
+        assert_eq!(Mana::compute(50, Intelligence(10)).max(), 60);
+        assert_eq!(Mana::compute(250, Intelligence(10)).max(), u8::MAX);
+    }
+
+    #[test]
+    fn chances_are_fractions_of_the_full_rng_range() {
+        // 4 agility gives a 10% crit chance, which is 25 out of 255
+        let crit_chance = CritChance::new(Agility(4));
+        assert!(crit_chance.roll(25));
+        assert!(!crit_chance.roll(26));
+
+        // The base dodge chance is also 10%
+        let dodge_chance = DodgeChance::new(Agility(0));
+        assert!(dodge_chance.roll(25));
+        assert!(!dodge_chance.roll(26));
+    }
+}
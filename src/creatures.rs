@@ -1,6 +1,9 @@
 //! Entities that can take part in combat
 
-use crate::{actions::AvailableActions, combat_statistics::*};
+use crate::rng::Advantage;
+use crate::spells::Spellbook;
+use crate::status_effects::StatusEffects;
+use crate::{actions::AvailableActions, combat_statistics::*, prediction::Revealed};
 use bevy::prelude::*;
 
 /// A marker component for the player entity
@@ -20,10 +23,15 @@ pub struct PlayerBundle {
     pub mana: Mana,
     pub ap: ActionPoints,
     pub actions: AvailableActions,
+    pub spellbook: Spellbook,
+    pub agility: Agility,
+    pub intelligence: Intelligence,
     pub damage: Damage,
     pub crit_chance: CritChance,
     pub dodge_chance: DodgeChance,
     pub flee_chance: FleeChance,
+    pub status_effects: StatusEffects,
+    pub advantage: Advantage,
 }
 
 /// The bundle of components used by the [`Monster`]
@@ -35,8 +43,14 @@ pub struct MonsterBundle {
     pub mana: Mana,
     pub ap: ActionPoints,
     pub actions: AvailableActions,
+    pub spellbook: Spellbook,
+    pub agility: Agility,
+    pub intelligence: Intelligence,
     pub damage: Damage,
     pub crit_chance: CritChance,
     pub dodge_chance: DodgeChance,
     pub flee_chance: FleeChance,
+    pub status_effects: StatusEffects,
+    pub advantage: Advantage,
+    pub revealed: Revealed,
 }
@@ -0,0 +1,192 @@
+//! Runs the combat rules without a window, driven by a script rather than a player
+//!
+//! Combine [`CombatPlugins`] and [`HeadlessPlugin`] with Bevy's `MinimalPlugins`
+//! to write deterministic integration tests or run balance simulations.
+
+use crate::actions::{ActionError, ActionId, ActionPlugin, ActionQueue};
+use crate::combat_flow::{Active, CombatFlowPlugin, TurnOrder};
+use crate::combat_setup::{CombatSetupPlugin, StartCombat};
+use crate::creatures::Player;
+use crate::monster_ai::MonsterAiPlugin;
+use crate::prediction::PredictionPlugin;
+use crate::rng::{RNGPlugin, Rng};
+use crate::spells::SpellPlugin;
+use crate::status_effects::StatusEffectPlugin;
+use bevy::app::PluginGroupBuilder;
+use bevy::prelude::*;
+use leafwing_terminal::PrintTerminalLine;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// The plugins that make up the rules of combat, independent of any user interface
+///
+/// When used with a terminal, add the terminal's plugin first so that actions can be used as terminal commands.
+pub struct CombatPlugins;
+
+impl PluginGroup for CombatPlugins {
+    fn build(&mut self, group: &mut PluginGroupBuilder) {
+        group
+            .add(RNGPlugin::<Rng>::default())
+            .add(CombatSetupPlugin)
+            .add(CombatFlowPlugin)
+            .add(StatusEffectPlugin)
+            .add(ActionPlugin)
+            .add(SpellPlugin)
+            .add(MonsterAiPlugin)
+            .add(PredictionPlugin);
+    }
+}
+
+/// Stands in for the keyboard and terminal, feeding the game the steps in the [`ScriptedInput`] resource
+///
+/// Messages that would be printed to the terminal are still sent as [`PrintTerminalLine`] events,
+/// so they can be read by tests.
+/// The first fight starts as soon as the app runs.
+pub struct HeadlessPlugin;
+
+impl Plugin for HeadlessPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Input<KeyCode>>()
+            .add_event::<PrintTerminalLine>()
+            .init_resource::<ScriptedInput>()
+            .add_startup_system(start_first_fight)
+            .add_system_to_stage(CoreStage::First, drive_scripted_input);
+    }
+}
+
+/// Starts combat as soon as the game begins, as there is no player to type `fight`
+fn start_first_fight(mut start_events: EventWriter<StartCombat>) {
+    start_events.send(StartCombat);
+}
+
+/// A single input to feed to the game
+///
+/// Scripts can be serialized, so that a recorded sequence of inputs can be replayed later.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ScriptStep {
+    /// Queue this action for the player, as if its terminal command was entered
+    Use(ActionId),
+    /// Queue this action for the player, to be used on the creature with this name
+    UseOn(ActionId, String),
+    /// Apply the next step of the queued action, as if the Enter key was pressed
+    Advance,
+    /// End the player's turn, as if the `end` command was entered
+    EndTurn,
+    /// Do nothing for one frame
+    Wait,
+}
+
+/// The inputs to feed to the game, one per frame, stored as a resource
+#[derive(Debug, Default, Clone)]
+pub struct ScriptedInput {
+    steps: VecDeque<ScriptStep>,
+    rejected: Vec<(ScriptStep, ActionError)>,
+}
+
+impl ScriptedInput {
+    /// Creates a new script from the provided `steps`
+    pub fn new(steps: impl IntoIterator<Item = ScriptStep>) -> Self {
+        ScriptedInput {
+            steps: steps.into_iter().collect(),
+            rejected: Vec::new(),
+        }
+    }
+
+    /// Adds a step to the end of the script
+    pub fn push(&mut self, step: ScriptStep) {
+        self.steps.push_back(step);
+    }
+
+    /// Queues the action with the `action_id`, then advances it `steps` times
+    pub fn use_action(&mut self, action_id: impl Into<ActionId>, steps: usize) {
+        self.push(ScriptStep::Use(action_id.into()));
+        for _ in 0..steps {
+            self.push(ScriptStep::Advance);
+        }
+    }
+
+    /// Have all of the steps been fed to the game?
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// The steps that the game refused, and why
+    #[must_use]
+    pub fn rejected(&self) -> &[(ScriptStep, ActionError)] {
+        &self.rejected
+    }
+}
+
+/// Feeds the next [`ScriptStep`] to the game
+///
+/// Runs at the very start of each frame, standing in for the input systems that `MinimalPlugins` lacks.
+fn drive_scripted_input(
+    mut script: ResMut<ScriptedInput>,
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut action_queue: ActionQueue,
+    mut turn_order: ResMut<TurnOrder>,
+    player_query: Query<(), (With<Player>, With<Active>)>,
+) {
+    // Keys are only pressed for a single frame
+    keyboard_input.release(KeyCode::Return);
+    keyboard_input.clear();
+
+    match script.steps.pop_front() {
+        Some(ScriptStep::Use(action_id)) => {
+            if let Err(error) = action_queue.queue(action_id.clone(), None) {
+                script.rejected.push((ScriptStep::Use(action_id), error));
+            }
+        }
+        Some(ScriptStep::UseOn(action_id, target_name)) => {
+            let result = action_queue
+                .resolve_target(&target_name)
+                .and_then(|target| action_queue.queue(action_id.clone(), Some(target)));
+
+            if let Err(error) = result {
+                script
+                    .rejected
+                    .push((ScriptStep::UseOn(action_id, target_name), error));
+            }
+        }
+        Some(ScriptStep::Advance) => keyboard_input.press(KeyCode::Return),
+        Some(ScriptStep::EndTurn) => {
+            if player_query.iter().next().is_none() {
+                script
+                    .rejected
+                    .push((ScriptStep::EndTurn, ActionError::NotYourTurn));
+            } else if action_queue.current().is_some() {
+                script
+                    .rejected
+                    .push((ScriptStep::EndTurn, ActionError::AlreadyQueued));
+            } else {
+                turn_order.end_turn();
+            }
+        }
+        Some(ScriptStep::Wait) | None => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scripts_survive_a_round_trip_through_ron() {
+        let script = vec![
+            ScriptStep::Use(ActionId::Attack),
+            ScriptStep::UseOn(ActionId::from_name("Fire Bolt"), "Goblin 2".to_string()),
+            ScriptStep::Advance,
+            ScriptStep::EndTurn,
+            ScriptStep::Wait,
+        ];
+
+        let ron = ron::to_string(&script).unwrap();
+        let parsed: Vec<ScriptStep> = ron::from_str(&ron).unwrap();
+        assert_eq!(parsed, script);
+
+        // Recorded scripts still name the same actions if the names are written differently
+        let parsed: ScriptStep = ron::from_str(r#"UseOn(Data("fire_bolt"), "Goblin 2")"#).unwrap();
+        assert_eq!(parsed, script[1]);
+    }
+}
@@ -10,7 +10,13 @@ pub mod combat_flow;
 pub mod combat_setup;
 pub mod combat_statistics;
 pub mod creatures;
+pub mod headless;
+pub mod monster_ai;
+pub mod prediction;
 pub mod rng;
+pub mod simulation;
+pub mod spells;
+pub mod status_effects;
 pub mod ui;
 
 mod system_sequence;
@@ -1,8 +1,18 @@
+use bevy::asset::AssetServerSettings;
 use bevy::prelude::*;
 use foresight_lib::*;
 
 fn main() {
-    App::new()
+    let seed = match seed_from_args(std::env::args().skip(1)) {
+        Ok(seed) => seed,
+        Err(message) => {
+            eprintln!("{message}");
+            std::process::exit(1);
+        }
+    };
+
+    let mut app = App::new();
+    app
         // Configure the game window
         /*
         .insert_resource(WindowDescriptor {
@@ -13,12 +23,51 @@ fn main() {
             ..Default::default()
         })
         */
+        // Must be inserted before the CombatPlugins, which read it
+        .insert_resource(seed)
+        // Reload changed assets, such as actions, during development
+        .insert_resource(AssetServerSettings {
+            watch_for_changes: cfg!(debug_assertions),
+            ..Default::default()
+        })
         // Standard Bevy functionality
         .add_plugins(DefaultPlugins)
         // Foresight game plugins
         .add_plugin(ui::UiPlugin)
-        .add_plugin(rng::RNGPlugin)
-        .add_plugin(combat_flow::CombatFlowPlugin)
-        .add_plugin(actions::ActionPlugin)
-        .run();
+        .add_plugins(headless::CombatPlugins)
+        .add_plugin(actions::ActionAssetPlugin)
+        .add_plugin(rng::analysis::RNGAnalysisPlugin)
+        .add_plugin(rng::SeedPlugin::<rng::Rng>::default());
+
+    // Commands that ignore the rules of the game are only available while debugging
+    if cfg!(debug_assertions) {
+        app.add_plugin(rng::TimeTravelPlugin::<rng::Rng>::default())
+            .add_plugin(actions::LearnPlugin);
+    }
+
+    app.run();
+}
+
+/// Reads the seed of the run from the command line arguments
+///
+/// Accepts either `--seed <SEED>` or `--seed=<SEED>`, falling back to the default seed when neither is supplied.
+fn seed_from_args(mut args: impl Iterator<Item = String>) -> Result<rng::Seed, String> {
+    let seed_string = match args.next() {
+        None => return Ok(rng::Seed::default()),
+        Some(arg) if arg == "--seed" => args
+            .next()
+            .ok_or_else(|| "A seed must be supplied after --seed.".to_string())?,
+        Some(arg) => match arg.strip_prefix("--seed=") {
+            Some(seed_string) => seed_string.to_string(),
+            None => return Err(format!("Unknown argument: {arg}")),
+        },
+    };
+
+    if let Some(arg) = args.next() {
+        return Err(format!("Unknown argument: {arg}"));
+    }
+
+    seed_string
+        .parse()
+        .map_err(|error| format!("Invalid seed: {error}."))
 }
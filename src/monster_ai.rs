@@ -0,0 +1,355 @@
+//! How monsters decide which action to use on their turn
+
+use crate::actions::{ActionId, ActionQueue, AvailableActions};
+use crate::combat_flow::{Active, TurnOrder};
+use crate::creatures::Monster;
+use crate::rng::{get_next_rng_value, DrawLabel, RNGOutputs, Rng, RngLog, RollKind};
+use bevy::prelude::*;
+use leafwing_terminal::PrintTerminalLine;
+use std::ops::RangeInclusive;
+
+/// Lets each [`Monster`] with a [`MonsterAi`] choose and use actions on its turn
+pub struct MonsterAiPlugin;
+
+impl Plugin for MonsterAiPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(choose_monster_action);
+    }
+}
+
+/// A way of choosing which action a monster uses
+pub trait DecisionStrategy: Send + Sync + 'static {
+    /// Does this strategy use an RNG value to decide?
+    ///
+    /// Strategies that do are passed a `roll` in [`DecisionStrategy::decide`].
+    fn uses_rng(&self) -> bool {
+        true
+    }
+
+    /// Chooses the action to use, out of the monster's `available_actions`
+    ///
+    /// Returns `None` if the monster should not act.
+    fn decide(
+        &mut self,
+        roll: Option<u8>,
+        available_actions: &AvailableActions,
+    ) -> Option<ActionId>;
+}
+
+/// The [`DecisionStrategy`] used by a monster
+#[derive(Component)]
+pub struct MonsterAi {
+    strategy: Box<dyn DecisionStrategy>,
+}
+
+impl MonsterAi {
+    /// Creates a new [`MonsterAi`] component, which uses the provided `strategy`
+    pub fn new(strategy: impl DecisionStrategy) -> Self {
+        MonsterAi {
+            strategy: Box::new(strategy),
+        }
+    }
+}
+
+/// Splits the range of RNG values into blocks, each of which corresponds to an action
+///
+/// Values that are not in any block result in no action.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Blocks {
+    blocks: Vec<(RangeInclusive<u8>, ActionId)>,
+}
+
+impl Blocks {
+    /// Creates a new [`Blocks`] strategy, with no blocks
+    #[must_use]
+    pub fn new() -> Self {
+        Blocks::default()
+    }
+
+    /// Adds a block, so that RNG values in the `range` choose the action with the `action_id`
+    ///
+    /// Earlier blocks take priority over later ones when they overlap.
+    #[must_use]
+    pub fn with_block(mut self, range: RangeInclusive<u8>, action_id: impl Into<ActionId>) -> Self {
+        self.blocks.push((range, action_id.into()));
+        self
+    }
+}
+
+impl DecisionStrategy for Blocks {
+    fn decide(
+        &mut self,
+        roll: Option<u8>,
+        _available_actions: &AvailableActions,
+    ) -> Option<ActionId> {
+        let roll = roll?;
+
+        self.blocks
+            .iter()
+            .find(|(range, _)| range.contains(&roll))
+            .map(|(_, action_id)| action_id.clone())
+    }
+}
+
+/// Chooses an action by taking the RNG value modulo the number of available actions
+///
+/// Actions are sorted alphabetically before being chosen from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Modulo;
+
+impl DecisionStrategy for Modulo {
+    fn decide(
+        &mut self,
+        roll: Option<u8>,
+        available_actions: &AvailableActions,
+    ) -> Option<ActionId> {
+        available_actions.random(roll?)
+    }
+}
+
+/// Chooses between actions in proportion to their weights
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Weighted {
+    table: Vec<(u32, ActionId)>,
+}
+
+impl Weighted {
+    /// Creates a new [`Weighted`] strategy, with no actions
+    #[must_use]
+    pub fn new() -> Self {
+        Weighted::default()
+    }
+
+    /// Adds the action with the `action_id`, which will be chosen in proportion to its `weight`
+    #[must_use]
+    pub fn with_weight(mut self, weight: u32, action_id: impl Into<ActionId>) -> Self {
+        self.table.push((weight, action_id.into()));
+        self
+    }
+}
+
+impl DecisionStrategy for Weighted {
+    fn decide(
+        &mut self,
+        roll: Option<u8>,
+        _available_actions: &AvailableActions,
+    ) -> Option<ActionId> {
+        let roll = roll?;
+        let total: u64 = self.table.iter().map(|(weight, _)| *weight as u64).sum();
+
+        // Scale the roll from 0..256 to 0..total
+        let mut target = roll as u64 * total / 256;
+
+        for (weight, action_id) in &self.table {
+            if target < *weight as u64 {
+                return Some(action_id.clone());
+            }
+            target -= *weight as u64;
+        }
+
+        None
+    }
+}
+
+/// Uses the same sequence of actions in order, starting over once it runs out
+///
+/// Does not use the RNG.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Scripted {
+    sequence: Vec<ActionId>,
+    index: usize,
+}
+
+impl Scripted {
+    /// Creates a new [`Scripted`] strategy, which uses the actions in the `sequence` in order
+    pub fn new(sequence: impl IntoIterator<Item = impl Into<ActionId>>) -> Self {
+        Scripted {
+            sequence: sequence.into_iter().map(Into::into).collect(),
+            index: 0,
+        }
+    }
+}
+
+impl DecisionStrategy for Scripted {
+    fn uses_rng(&self) -> bool {
+        false
+    }
+
+    fn decide(
+        &mut self,
+        _roll: Option<u8>,
+        _available_actions: &AvailableActions,
+    ) -> Option<ActionId> {
+        let action_id = self.sequence.get(self.index)?.clone();
+        self.index = (self.index + 1) % self.sequence.len();
+        Some(action_id)
+    }
+}
+
+/// When Enter is pressed on a monster's turn, the monster chooses an action and queues it
+///
+/// Monsters that choose not to act, or choose an action they cannot use, end their turn.
+#[allow(clippy::too_many_arguments)]
+fn choose_monster_action(
+    keyboard_input: Option<Res<Input<KeyCode>>>,
+    mut monster_query: Query<
+        (Entity, &mut MonsterAi, &AvailableActions, Option<&Name>),
+        (With<Monster>, With<Active>),
+    >,
+    mut action_queue: ActionQueue,
+    mut turn_order: ResMut<TurnOrder>,
+    mut rng: ResMut<Rng>,
+    mut rng_outputs: ResMut<RNGOutputs>,
+    mut rng_log: ResMut<RngLog>,
+    mut lines: EventWriter<PrintTerminalLine>,
+) {
+    let advance_pressed = keyboard_input.map_or(false, |keyboard_input| {
+        keyboard_input.just_pressed(KeyCode::Return)
+    });
+
+    // Presses used to advance an action should not also be used to choose the next one
+    if !advance_pressed || action_queue.current().is_some() || action_queue.is_changed() {
+        return;
+    }
+
+    let (monster, mut monster_ai, available_actions, name) = match monster_query.get_single_mut() {
+        Ok(monster) => monster,
+        Err(_) => return,
+    };
+    let name = name.map_or("The monster", |name| name.as_str());
+
+    let roll = monster_ai.strategy.uses_rng().then(|| {
+        get_next_rng_value(
+            &mut *rng,
+            &mut *rng_outputs,
+            &mut *rng_log,
+            DrawLabel::new(monster, "Decide", RollKind::Choice),
+        )
+    });
+    let choice = monster_ai.strategy.decide(roll, available_actions);
+
+    let rolled = match roll {
+        Some(roll) => format!(", rolling {roll}"),
+        None => String::new(),
+    };
+
+    match choice.map(|action_id| {
+        (
+            action_queue.queue_for(monster, action_id.clone(), None),
+            action_id,
+        )
+    }) {
+        Some((Ok(()), action_id)) => {
+            lines.send(PrintTerminalLine::new(format!(
+                "{name} considers its options{rolled}: it chooses {action_id}."
+            )));
+        }
+        Some((Err(error), action_id)) => {
+            lines.send(PrintTerminalLine::new(format!(
+                "{name} considers its options{rolled}: it tries to use {action_id}, but fails. {error}"
+            )));
+            turn_order.end_turn();
+        }
+        None => {
+            lines.send(PrintTerminalLine::new(format!(
+                "{name} considers its options{rolled}: it does nothing."
+            )));
+            turn_order.end_turn();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The actions chosen by the `strategy` for each of the `rolls`, knowing only how to attack and flee
+    fn decisions(
+        mut strategy: impl DecisionStrategy,
+        rolls: impl IntoIterator<Item = Option<u8>>,
+    ) -> Vec<Option<ActionId>> {
+        let mut available_actions = AvailableActions::default();
+        available_actions.insert(ActionId::Attack);
+        available_actions.insert(ActionId::Flee);
+
+        rolls
+            .into_iter()
+            .map(|roll| strategy.decide(roll, &available_actions))
+            .collect()
+    }
+
+    #[test]
+    fn blocks_choose_the_first_block_containing_the_roll() {
+        let blocks = Blocks::new()
+            .with_block(0..=99, ActionId::Attack)
+            .with_block(50..=199, ActionId::Flee);
+
+        assert_eq!(
+            decisions(blocks, [Some(75), Some(150), Some(200), None]),
+            [Some(ActionId::Attack), Some(ActionId::Flee), None, None]
+        );
+    }
+
+    #[test]
+    fn modulo_wraps_around_the_sorted_actions() {
+        assert_eq!(
+            decisions(Modulo, [Some(0), Some(1), Some(255), None]),
+            [
+                Some(ActionId::Attack),
+                Some(ActionId::Flee),
+                Some(ActionId::Flee),
+                None
+            ]
+        );
+
+        assert_eq!(Modulo.decide(Some(0), &AvailableActions::default()), None);
+    }
+
+    #[test]
+    fn weights_split_the_rolls_at_their_boundaries() {
+        // Attack covers a quarter of the rolls, from 0 to 63
+        let weighted = Weighted::new()
+            .with_weight(1, ActionId::Attack)
+            .with_weight(3, ActionId::Flee);
+
+        assert_eq!(
+            decisions(weighted, [Some(0), Some(63), Some(64), Some(255), None]),
+            [
+                Some(ActionId::Attack),
+                Some(ActionId::Attack),
+                Some(ActionId::Flee),
+                Some(ActionId::Flee),
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn weights_totalling_zero_choose_nothing() {
+        let weighted = Weighted::new()
+            .with_weight(0, ActionId::Attack)
+            .with_weight(0, ActionId::Flee);
+        assert_eq!(decisions(weighted, [Some(0), Some(255)]), [None, None]);
+
+        assert_eq!(decisions(Weighted::new(), [Some(0)]), [None]);
+    }
+
+    #[test]
+    fn scripts_start_over_once_they_end() {
+        let scripted = Scripted::new([ActionId::Attack, ActionId::Attack, ActionId::Flee]);
+        assert!(!scripted.uses_rng());
+
+        assert_eq!(
+            decisions(scripted, [None; 4]),
+            [
+                Some(ActionId::Attack),
+                Some(ActionId::Attack),
+                Some(ActionId::Flee),
+                Some(ActionId::Attack)
+            ]
+        );
+
+        let empty = Scripted::new(Vec::<ActionId>::new());
+        assert_eq!(decisions(empty, [None]), [None]);
+    }
+}
@@ -0,0 +1,520 @@
+//! Predicts what upcoming RNG values will do when they are used by the queued [`Action`](crate::actions::Action)
+
+use crate::actions::Actions;
+use crate::combat_statistics::{CritChance, Damage, DodgeChance, FleeChance, Intelligence};
+use crate::creatures::Player;
+use crate::rng::{Advantage, RNGOutputs, RollKind, RollMode};
+use crate::status_effects::StatusEffects;
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+use std::fmt::Display;
+use std::sync::Arc;
+
+/// Keeps the [`Prediction`] resource in sync with the queued action
+pub struct PredictionPlugin;
+
+impl Plugin for PredictionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Prediction>()
+            .add_system_to_stage(CoreStage::PreUpdate, update_prediction);
+    }
+}
+
+/// A statistic of a creature that the player may or may not know about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[allow(missing_docs)]
+pub enum Stat {
+    Damage,
+    CritChance,
+    DodgeChance,
+    FleeChance,
+    Intelligence,
+}
+
+impl Stat {
+    /// Every stat, which is revealed all at once by scanning spells
+    pub const ALL: [Stat; 5] = [
+        Stat::Damage,
+        Stat::CritChance,
+        Stat::DodgeChance,
+        Stat::FleeChance,
+        Stat::Intelligence,
+    ];
+}
+
+/// The statistics of a [`Monster`](crate::creatures::Monster) that have been revealed to the player
+///
+/// Stats that have been revealed stay revealed, even after the monster dies.
+/// The player always knows their own stats.
+#[derive(Component, Debug, Default, Clone)]
+pub struct Revealed {
+    set: HashSet<Stat>,
+}
+
+impl Revealed {
+    /// Reveals the `stat`
+    pub fn reveal(&mut self, stat: Stat) {
+        self.set.insert(stat);
+    }
+
+    /// Has the `stat` been revealed?
+    #[must_use]
+    pub fn contains(&self, stat: Stat) -> bool {
+        self.set.contains(&stat)
+    }
+}
+
+/// The stats of a single creature, as far as the player knows them
+///
+/// Unknown stats are `None`.
+/// Sources of [`Advantage`] and bonuses from [`StatusEffects`] are always known, as they can be seen on the creature.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[allow(missing_docs)]
+pub struct KnownStats {
+    pub damage: Option<Damage>,
+    pub crit_chance: Option<CritChance>,
+    pub dodge_chance: Option<DodgeChance>,
+    pub flee_chance: Option<FleeChance>,
+    pub intelligence: Option<Intelligence>,
+    pub advantage: Advantage,
+}
+
+/// Everything the player knows about the creatures involved in an action
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PredictionContext {
+    /// The creature using the action
+    pub actor: KnownStats,
+    /// The creature that the action is used on
+    pub target: KnownStats,
+}
+
+/// The result of a single roll, from the perspective of the creature using the action
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// The roll goes the actor's way
+    Success,
+    /// The roll goes against the actor
+    Failure,
+    /// The roll produces this amount
+    Amount(u8),
+    /// The result depends on stats that the player does not know
+    Unknown,
+}
+
+/// What an upcoming RNG value will be used for, and what it will do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Annotation {
+    /// The kind of roll that will use the value
+    pub roll: RollKind,
+    /// What the roll will result in
+    pub outcome: Outcome,
+    /// Where the value sits among the values used by the roll, if the roll uses several of them
+    pub part: Option<RollPart>,
+}
+
+/// The position of a value among the values used by a roll made with advantage or disadvantage
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RollPart {
+    /// The position of the value within the roll, starting from 0
+    pub position: usize,
+    /// How many values the roll uses, and how they are combined
+    pub mode: RollMode,
+}
+
+impl Display for RollPart {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "{}/{}, {} of {}",
+            self.position + 1,
+            self.mode.draws,
+            self.mode.combine,
+            self.mode.draws
+        ))
+    }
+}
+
+impl Display for Annotation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(part) = self.part {
+            f.write_fmt(format_args!("[{part}] "))?;
+        }
+
+        match (self.roll, self.outcome) {
+            (roll, Outcome::Unknown) => f.write_fmt(format_args!("{roll}: ???")),
+            (RollKind::Hit | RollKind::Dodge, Outcome::Success) => f.write_str("hits"),
+            (RollKind::Hit | RollKind::Dodge, Outcome::Failure) => f.write_str("misses"),
+            (RollKind::Crit, Outcome::Success) => f.write_str("crit!"),
+            (RollKind::Crit, Outcome::Failure) => f.write_str("no crit"),
+            (RollKind::SpellFail, Outcome::Success) => f.write_str("succeeds"),
+            (RollKind::SpellFail, Outcome::Failure) => f.write_str("fizzles"),
+            (RollKind::Damage, Outcome::Amount(amount)) => {
+                f.write_fmt(format_args!("{amount} damage"))
+            }
+            (RollKind::CoinFlip, Outcome::Success) => f.write_str("heads"),
+            (RollKind::CoinFlip, Outcome::Failure) => f.write_str("tails"),
+            (roll, Outcome::Amount(amount)) => f.write_fmt(format_args!("{roll}: {amount}")),
+            (roll, Outcome::Success) => f.write_fmt(format_args!("{roll}: success")),
+            (roll, Outcome::Failure) => f.write_fmt(format_args!("{roll}: failure")),
+        }
+    }
+}
+
+/// Predicts the outcome of an action, given what the player knows and the upcoming RNG values
+///
+/// The returned annotations correspond to the RNG values that the action will use, in order.
+/// Actions that will stop early (such as an attack that misses) should return fewer annotations than values.
+pub type Predictor = Arc<dyn Fn(&PredictionContext, &[u8]) -> Vec<Annotation> + Send + Sync>;
+
+/// The predicted result of the queued action for each upcoming RNG value, stored as a resource
+#[derive(Debug, Default)]
+pub struct Prediction {
+    /// Pairs of [`RNGOutputs::index`] and the predicted result of the value at that index
+    annotations: Vec<(usize, Annotation)>,
+}
+
+impl Prediction {
+    /// The predicted result of the RNG value at the provided `index`, if it will be used by the queued action
+    #[must_use]
+    pub fn get(&self, index: usize) -> Option<Annotation> {
+        self.annotations
+            .iter()
+            .find(|(annotated_index, _)| *annotated_index == index)
+            .map(|(_, annotation)| *annotation)
+    }
+
+    /// Is there a prediction for any value?
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.annotations.is_empty()
+    }
+}
+
+impl KnownStats {
+    /// Collects the stats of a creature that are known to the player
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        damage: Option<&Damage>,
+        crit_chance: Option<&CritChance>,
+        dodge_chance: Option<&DodgeChance>,
+        flee_chance: Option<&FleeChance>,
+        intelligence: Option<&Intelligence>,
+        advantage: Option<&Advantage>,
+        status_effects: Option<&StatusEffects>,
+        revealed: Option<&Revealed>,
+        is_player: bool,
+    ) -> Self {
+        let known = |stat| is_player || revealed.map_or(false, |revealed| revealed.contains(stat));
+        let crit_bonus = status_effects.map_or(0, StatusEffects::crit_bonus);
+        let dodge_bonus = status_effects.map_or(0, StatusEffects::dodge_bonus);
+
+        KnownStats {
+            damage: damage.filter(|_| known(Stat::Damage)).cloned(),
+            crit_chance: crit_chance
+                .filter(|_| known(Stat::CritChance))
+                .map(|crit_chance| crit_chance.with_bonus(crit_bonus)),
+            dodge_chance: dodge_chance
+                .filter(|_| known(Stat::DodgeChance))
+                .map(|dodge_chance| dodge_chance.with_bonus(dodge_bonus)),
+            flee_chance: flee_chance.filter(|_| known(Stat::FleeChance)).cloned(),
+            intelligence: intelligence.filter(|_| known(Stat::Intelligence)).copied(),
+            advantage: advantage.cloned().unwrap_or_default(),
+        }
+    }
+}
+
+/// Recomputes the [`Prediction`] whenever a new action is queued, or the RNG moves before it starts
+///
+/// The RNG can move without the action being used, such as when it is rewound or a status effect rolls.
+fn update_prediction(
+    mut prediction: ResMut<Prediction>,
+    actions: Res<Actions>,
+    rng_outputs: Res<RNGOutputs>,
+    creature_query: Query<(
+        Option<&Damage>,
+        Option<&CritChance>,
+        Option<&DodgeChance>,
+        Option<&FleeChance>,
+        Option<&Intelligence>,
+        Option<&Advantage>,
+        Option<&StatusEffects>,
+        Option<&Revealed>,
+        Option<&Player>,
+    )>,
+    effects_query: Query<&StatusEffects>,
+) {
+    if !actions.is_changed() && !rng_outputs.is_changed() {
+        return;
+    }
+
+    let action = match actions.current().map(|action_id| actions.get(&action_id)) {
+        Some(Ok(action)) => action,
+        _ => {
+            prediction.annotations.clear();
+            return;
+        }
+    };
+
+    // The existing prediction is indexed by RNG position, so it stays accurate as the action is applied
+    if action.has_started() {
+        return;
+    }
+
+    // Queued actions already know who is using them, and on whom
+    let (predictor, actor) = match (action.predictor(), action.context().actor) {
+        (Some(predictor), Some(actor)) => (predictor, actor),
+        _ => {
+            prediction.annotations.clear();
+            return;
+        }
+    };
+    let known_stats = |entity: Option<Entity>| match entity.map(|entity| creature_query.get(entity))
+    {
+        Some(Ok((
+            damage,
+            crit_chance,
+            dodge_chance,
+            flee_chance,
+            intelligence,
+            advantage,
+            status_effects,
+            revealed,
+            player,
+        ))) => KnownStats::new(
+            damage,
+            crit_chance,
+            dodge_chance,
+            flee_chance,
+            intelligence,
+            advantage,
+            status_effects,
+            revealed,
+            player.is_some(),
+        ),
+        _ => KnownStats::default(),
+    };
+
+    let upcoming: Vec<u8> = rng_outputs
+        .upcoming()
+        .map(|internals| internals.result)
+        .collect();
+    let start = rng_outputs.index();
+
+    // Confused actors roll before the first step, and may turn the action on themselves
+    let mut target = action.context().target();
+    let mut annotations = Vec::new();
+    let confusion = effects_query
+        .get(actor)
+        .ok()
+        .and_then(StatusEffects::confusion);
+    if let (Some(chance), Some(&value), true) = (
+        confusion,
+        upcoming.first(),
+        action.targeting().targets_enemies(),
+    ) {
+        let confused = value < chance;
+        if confused {
+            target = Some(actor);
+        }
+
+        annotations.push(Annotation {
+            roll: RollKind::Status,
+            outcome: if confused {
+                Outcome::Failure
+            } else {
+                Outcome::Success
+            },
+            part: None,
+        });
+    }
+
+    let context = PredictionContext {
+        actor: known_stats(Some(actor)),
+        target: known_stats(target),
+    };
+    annotations.extend(predictor(&context, &upcoming[annotations.len()..]));
+
+    prediction.annotations = annotations
+        .into_iter()
+        .enumerate()
+        .map(|(offset, annotation)| (start + offset, annotation))
+        .collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actions::{predict_steps, Action, ActionId, DamageRange, Step};
+    use crate::combat_statistics::{Agility, CreatureResources};
+    use crate::rng::{AdvantageSource, Combine, Rng, RngWindow};
+    use crate::status_effects::{EffectKind, StatusEffect};
+
+    const AGILITY: Agility = Agility(4);
+
+    /// The stats of a creature with every stat, as far as the player knows them
+    fn known_stats(
+        status_effects: Option<&StatusEffects>,
+        revealed: Option<&Revealed>,
+        is_player: bool,
+    ) -> KnownStats {
+        KnownStats::new(
+            Some(&Damage::new(2, 4)),
+            Some(&CritChance::new(AGILITY)),
+            Some(&DodgeChance::new(AGILITY)),
+            Some(&FleeChance::new(AGILITY)),
+            Some(&Intelligence(5)),
+            None,
+            status_effects,
+            revealed,
+            is_player,
+        )
+    }
+
+    /// Queues an attack by a creature with the `status_effects` on another creature, after seeking the RNG to `index`
+    ///
+    /// Returns the world, with its [`Prediction`] updated.
+    fn predict_attack(status_effects: StatusEffects, index: usize) -> World {
+        let mut world = World::new();
+        let mut rng = Rng::default();
+        let mut rng_outputs = RNGOutputs::new(&mut rng, RngWindow::default());
+        rng_outputs.seek(&mut rng, index);
+        world.insert_resource(rng);
+        world.insert_resource(rng_outputs);
+        world.init_resource::<Prediction>();
+
+        let actor = world
+            .spawn()
+            .insert_bundle((CritChance::new(AGILITY), status_effects, Player))
+            .id();
+        let target = world.spawn().insert(DodgeChance::new(AGILITY)).id();
+
+        let mut actions = Actions::default();
+        actions.insert(Action::attack());
+        let resources = CreatureResources {
+            life: Some(10),
+            mana: Some(10),
+            action_points: Some(10),
+        };
+        actions
+            .queue(ActionId::Attack, actor, vec![target], &resources)
+            .unwrap();
+        world.insert_resource(actions);
+
+        SystemStage::single(update_prediction).run(&mut world);
+        world
+    }
+
+    fn annotation(world: &World, index: usize) -> Option<Annotation> {
+        world.get_resource::<Prediction>().unwrap().get(index)
+    }
+
+    #[test]
+    fn unrevealed_stats_are_unknown() {
+        let monster = known_stats(None, None, false);
+        assert_eq!(monster, KnownStats::default());
+
+        let mut revealed = Revealed::default();
+        revealed.reveal(Stat::CritChance);
+        let monster = known_stats(None, Some(&revealed), false);
+        assert_eq!(monster.crit_chance, Some(CritChance::new(AGILITY)));
+        assert_eq!(monster.dodge_chance, None);
+
+        let context = PredictionContext {
+            actor: known_stats(None, None, false),
+            target: known_stats(None, None, true),
+        };
+        let annotations = predict_steps(&[Step::Crit { multiplier: 2 }], &context, &[0]);
+        assert_eq!(annotations[0].outcome, Outcome::Unknown);
+    }
+
+    #[test]
+    fn players_know_their_own_stats() {
+        let player = known_stats(None, None, true);
+
+        assert_eq!(player.damage, Some(Damage::new(2, 4)));
+        assert_eq!(player.flee_chance, Some(FleeChance::new(AGILITY)));
+        assert_eq!(player.intelligence, Some(Intelligence(5)));
+    }
+
+    #[test]
+    fn status_effect_bonuses_are_included() {
+        let mut status_effects = StatusEffects::default();
+        status_effects.apply(StatusEffect::new(EffectKind::LuckyStrike(63), 1));
+        status_effects.apply(StatusEffect::new(EffectKind::Evasion(51), 1));
+        let player = known_stats(Some(&status_effects), None, true);
+
+        assert_eq!(
+            player.crit_chance,
+            Some(CritChance::new(AGILITY).with_bonus(63))
+        );
+        assert_eq!(
+            player.dodge_chance,
+            Some(DodgeChance::new(AGILITY).with_bonus(51))
+        );
+    }
+
+    #[test]
+    fn annotations_are_indexed_from_the_rng_index() {
+        let world = predict_attack(StatusEffects::default(), 50);
+
+        assert_eq!(annotation(&world, 49), None);
+        assert_eq!(annotation(&world, 50).unwrap().roll, RollKind::Hit);
+        assert_eq!(annotation(&world, 51).unwrap().roll, RollKind::Dodge);
+    }
+
+    #[test]
+    fn confused_actors_roll_before_their_action() {
+        let mut status_effects = StatusEffects::default();
+        status_effects.apply(StatusEffect::new(EffectKind::Confusion(51), 5));
+        let world = predict_attack(status_effects, 50);
+        let value = world.get_resource::<RNGOutputs>().unwrap().up_next().result;
+
+        let confusion = annotation(&world, 50).unwrap();
+        assert_eq!(confusion.roll, RollKind::Status);
+        assert_eq!(
+            confusion.outcome,
+            if value < 51 {
+                Outcome::Failure
+            } else {
+                Outcome::Success
+            }
+        );
+        assert_eq!(annotation(&world, 51).unwrap().roll, RollKind::Hit);
+    }
+
+    #[test]
+    fn rolls_with_advantage_mark_each_of_their_values() {
+        let mut actor = known_stats(None, None, true);
+        actor
+            .advantage
+            .add(AdvantageSource::new("Blessed", 2).for_roll(RollKind::Crit));
+        let context = PredictionContext {
+            actor,
+            target: known_stats(None, None, false),
+        };
+
+        let annotations = predict_steps(
+            &[
+                Step::Crit { multiplier: 2 },
+                Step::Damage(DamageRange::Weapon),
+            ],
+            &context,
+            &[0; 5],
+        );
+
+        // Only the crit is rolled with advantage, so the damage roll is a single value
+        let mode = RollMode::new(3, Combine::Best);
+        let parts: Vec<Option<RollPart>> = annotations
+            .iter()
+            .map(|annotation| annotation.part)
+            .collect();
+        assert_eq!(
+            parts,
+            [
+                Some(RollPart { position: 0, mode }),
+                Some(RollPart { position: 1, mode }),
+                Some(RollPart { position: 2, mode }),
+                None
+            ]
+        );
+    }
+}
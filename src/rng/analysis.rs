@@ -0,0 +1,354 @@
+//! Exhaustive analysis of the state space of the SMW-style [`Rng`]
+//!
+//! Every `(s, t)` state eventually falls into a cycle.
+//! Some states lie on a cycle themselves, while others sit on a "tail" that feeds into a cycle at its entry point.
+
+use super::{RNGInternals, RNGOutputs, RandomSource, Rng};
+use bevy::prelude::*;
+use leafwing_terminal::*;
+
+/// Adds the `analyze` terminal command, which reports on the structure of the [`Rng`]
+pub struct RNGAnalysisPlugin;
+
+impl Plugin for RNGAnalysisPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_terminal_command::<AnalyzeCommand, _, _>(analyze_command);
+    }
+}
+
+/// The number of distinct `(s, t)` states of an [`Rng`]
+pub const STATE_COUNT: usize = 1 << 16;
+
+/// A closed loop of states that an [`Rng`] repeats forever once it is entered
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cycle {
+    /// The first state on the cycle that was discovered
+    ///
+    /// Positions along the cycle are measured from this state.
+    pub representative: Rng,
+    /// The number of steps needed to return to any state on the cycle
+    pub length: usize,
+    /// The number of states that eventually reach this cycle, including the states on the cycle itself
+    pub basin: usize,
+}
+
+/// Where a single state sits in the state space
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct StateInfo {
+    /// Index into [`StateSpace::cycles`]
+    cycle: usize,
+    /// The number of steps until this state reaches its cycle
+    tail: usize,
+    /// The first state on the cycle that is reached from this state
+    entry: usize,
+    /// The number of steps from the cycle's representative to this state
+    ///
+    /// Only meaningful for states that lie on a cycle.
+    position: usize,
+}
+
+/// The orbits of every possible [`Rng`] state
+///
+/// Computing this visits all [`STATE_COUNT`] states once, so it should be cached rather than rebuilt.
+#[derive(Debug, Clone)]
+pub struct StateSpace {
+    cycles: Vec<Cycle>,
+    states: Vec<StateInfo>,
+}
+
+impl StateSpace {
+    /// Walks every state of the [`Rng`], recording which cycle it ends up on
+    #[must_use]
+    pub fn explore() -> Self {
+        const UNVISITED: u8 = 0;
+        const ON_PATH: u8 = 1;
+        const DONE: u8 = 2;
+
+        let mut marks = vec![UNVISITED; STATE_COUNT];
+        let mut states = vec![
+            StateInfo {
+                cycle: 0,
+                tail: 0,
+                entry: 0,
+                position: 0,
+            };
+            STATE_COUNT
+        ];
+        let mut cycles: Vec<Cycle> = Vec::new();
+        let mut path: Vec<usize> = Vec::new();
+
+        for start in 0..STATE_COUNT {
+            if marks[start] != UNVISITED {
+                continue;
+            }
+
+            // Follow the orbit until we hit a state whose fate is already known, or loop back on ourselves
+            path.clear();
+            let mut current = start;
+            while marks[current] == UNVISITED {
+                marks[current] = ON_PATH;
+                path.push(current);
+                current = Self::index(&Self::successor(current));
+            }
+
+            // We found a brand new cycle, made of every state on the path from `current` onwards
+            let mut tail_end = path.len();
+            if marks[current] == ON_PATH {
+                let cycle_start = path.iter().position(|&i| i == current).unwrap();
+                let cycle = cycles.len();
+
+                for (position, &state) in path[cycle_start..].iter().enumerate() {
+                    states[state] = StateInfo {
+                        cycle,
+                        tail: 0,
+                        entry: state,
+                        position,
+                    };
+                    marks[state] = DONE;
+                }
+
+                cycles.push(Cycle {
+                    representative: Self::state(current),
+                    length: path.len() - cycle_start,
+                    basin: path.len() - cycle_start,
+                });
+                tail_end = cycle_start;
+            }
+
+            // Everything left on the path drains into the cycle one step at a time
+            for &state in path[..tail_end].iter().rev() {
+                let next = states[Self::index(&Self::successor(state))];
+                states[state] = StateInfo {
+                    cycle: next.cycle,
+                    tail: next.tail + 1,
+                    entry: next.entry,
+                    position: 0,
+                };
+                cycles[next.cycle].basin += 1;
+                marks[state] = DONE;
+            }
+        }
+
+        StateSpace { cycles, states }
+    }
+
+    /// All of the cycles in the state space, in the order they were discovered
+    #[must_use]
+    pub fn cycles(&self) -> &[Cycle] {
+        &self.cycles
+    }
+
+    /// The cycle that the `rng` will eventually repeat forever
+    #[must_use]
+    pub fn cycle_of(&self, rng: &Rng) -> &Cycle {
+        &self.cycles[self.info(rng).cycle]
+    }
+
+    /// The number of steps before the `rng` reaches its cycle
+    ///
+    /// This is 0 for states that lie on a cycle.
+    #[must_use]
+    pub fn tail_length(&self, rng: &Rng) -> usize {
+        self.info(rng).tail
+    }
+
+    /// The first state on its cycle that the `rng` will reach
+    ///
+    /// States that lie on a cycle are their own entry point.
+    #[must_use]
+    pub fn entry_point(&self, rng: &Rng) -> Rng {
+        Self::state(self.info(rng).entry)
+    }
+
+    /// How many steps must be taken from `from` before the RNG is in the state `to`?
+    ///
+    /// Returns `None` if `to` can never be reached from `from`.
+    #[must_use]
+    pub fn distance(&self, from: &Rng, to: &Rng) -> Option<usize> {
+        let from_info = self.info(from);
+        let to_info = self.info(to);
+
+        if from_info.cycle != to_info.cycle {
+            return None;
+        }
+
+        if to_info.tail == 0 {
+            // Walk down the tail, then around the cycle
+            let length = self.cycles[to_info.cycle].length;
+            let entry_position = self.states[from_info.entry].position;
+            let around = (to_info.position + length - entry_position) % length;
+
+            Some(from_info.tail + around)
+        } else {
+            // Tails are never revisited, so `to` must lie on the path from `from` to its cycle
+            let steps = from_info.tail.checked_sub(to_info.tail)?;
+            let mut rng = from.clone();
+            for _ in 0..steps {
+                rng.step();
+            }
+
+            (&rng == to).then(|| steps)
+        }
+    }
+
+    /// How many steps must be taken from `from` before the predicate holds for the RNG's internals?
+    ///
+    /// The search covers every state that `from` can ever reach, so `None` means that the predicate will never hold.
+    #[must_use]
+    pub fn find_first(
+        &self,
+        from: &Rng,
+        predicate: impl FnMut(&RNGInternals) -> bool,
+    ) -> Option<usize> {
+        let max_steps = self.tail_length(from) + self.cycle_of(from).length;
+
+        steps_until(from, predicate, max_steps)
+    }
+
+    fn info(&self, rng: &Rng) -> StateInfo {
+        self.states[Self::index(rng)]
+    }
+
+    fn index(rng: &Rng) -> usize {
+        (rng.s as usize) << 8 | rng.t as usize
+    }
+
+    fn state(index: usize) -> Rng {
+        Rng::new((index >> 8) as u8, index as u8)
+    }
+
+    fn successor(index: usize) -> Rng {
+        let mut rng = Self::state(index);
+        rng.step();
+        rng
+    }
+}
+
+/// How many steps must be taken from `from` before the predicate holds for the RNG's internals?
+///
+/// Works for any [`RandomSource`], but gives up and returns `None` after `max_steps` steps.
+#[must_use]
+pub fn steps_until<R: RandomSource>(
+    from: &R,
+    mut predicate: impl FnMut(&RNGInternals) -> bool,
+    max_steps: usize,
+) -> Option<usize> {
+    let mut rng = from.clone();
+
+    (1..=max_steps).find(|_| predicate(&rng.gen_verbose()))
+}
+
+/// Reports on the cycle that the RNG is currently on
+///
+/// If a `value` is supplied, finds how many RNG values will be used before that value comes up next.
+#[derive(TerminalCommand)]
+#[terminal_command(name = "analyze")]
+struct AnalyzeCommand {
+    /// The RNG value to search for
+    value: Option<i64>,
+}
+
+fn analyze_command(
+    mut terminal_command: TerminalCommand<AnalyzeCommand>,
+    mut state_space: Local<Option<StateSpace>>,
+    rng_outputs: Res<RNGOutputs>,
+) {
+    let AnalyzeCommand { value } = match terminal_command.take() {
+        Some(command) => command,
+        None => return,
+    };
+
+    let state_space = state_space.get_or_insert_with(StateSpace::explore);
+    let up_next = rng_outputs.up_next();
+    let rng = Rng::new(up_next.s, up_next.t);
+
+    match value {
+        None => {
+            let cycle = state_space.cycle_of(&rng);
+            let tail = state_space.tail_length(&rng);
+
+            terminal_command.reply(format!(
+                "The RNG is {tail} steps away from a cycle of length {}, one of {} cycles.",
+                cycle.length,
+                state_space.cycles().len()
+            ));
+        }
+        Some(value) => {
+            let value = match u8::try_from(value) {
+                Ok(value) => value,
+                Err(_) => {
+                    terminal_command.reply_failed("RNG values range from 0 to 255.");
+                    return;
+                }
+            };
+
+            if up_next.result == value {
+                terminal_command.reply(format!("{value} is up next."));
+                return;
+            }
+
+            match state_space.find_first(&rng, |internals| internals.result == value) {
+                Some(steps) => terminal_command.reply(format!(
+                    "{value} will come up after {steps} more RNG values are used."
+                )),
+                None => terminal_command.reply(format!("{value} will never come up again.")),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_state_belongs_to_one_cycle() {
+        let state_space = StateSpace::explore();
+        let basins: usize = state_space.cycles().iter().map(|cycle| cycle.basin).sum();
+
+        assert_eq!(basins, STATE_COUNT);
+    }
+
+    #[test]
+    fn default_state_is_on_the_longest_cycle() {
+        let state_space = StateSpace::explore();
+        let rng = Rng::default();
+        let longest = state_space.cycles().iter().map(|cycle| cycle.length).max();
+
+        assert_eq!(state_space.tail_length(&rng), 0);
+        assert_eq!(state_space.entry_point(&rng), rng);
+        assert_eq!(state_space.cycle_of(&rng).length, 32512);
+        assert_eq!(longest, Some(32512));
+    }
+
+    #[test]
+    fn distance_between_known_states() {
+        let state_space = StateSpace::explore();
+        let from = Rng::default();
+        // The state reached after stepping the default state 1000 times
+        let to = Rng::new(130, 247);
+
+        assert_eq!(state_space.distance(&from, &from), Some(0));
+        assert_eq!(state_space.distance(&from, &to), Some(1000));
+        assert_eq!(state_space.distance(&to, &from), Some(32512 - 1000));
+    }
+
+    #[test]
+    fn find_first_matches_stepping() {
+        let state_space = StateSpace::explore();
+        let rng = Rng::default();
+
+        assert_eq!(
+            state_space.find_first(&rng, |internals| internals.result == 0),
+            Some(108)
+        );
+        assert_eq!(
+            state_space.find_first(&rng, |internals| internals.result == 42),
+            steps_until(&rng, |internals| internals.result == 42, 1000)
+        );
+        assert_eq!(
+            steps_until(&rng, |internals| internals.result == 42, 61),
+            None
+        );
+    }
+}
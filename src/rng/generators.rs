@@ -0,0 +1,381 @@
+//! Classic retro random number generators, for comparison with the SMW-style [`Rng`](super::Rng)
+
+use super::{RNGInternals, RandomSource, Rng, Seed};
+
+/// A linear congruential generator, as used by many 8- and 16-bit era games
+///
+/// Each step computes `state = state * multiplier + increment`, wrapping on overflow.
+/// The result is the highest byte of the 32-bit state: `s` and `t` are the two highest bytes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lcg {
+    state: u32,
+    multiplier: u32,
+    increment: u32,
+}
+
+impl Lcg {
+    /// The multiplier used by the 3rd and 4th generation Pokémon games
+    pub const POKEMON_MULTIPLIER: u32 = 0x41C6_4E6D;
+    /// The increment used by the 3rd and 4th generation Pokémon games
+    pub const POKEMON_INCREMENT: u32 = 0x6073;
+
+    /// Creates a new LCG with an arbitrary `multiplier` and `increment`
+    #[must_use]
+    pub fn new(state: u32, multiplier: u32, increment: u32) -> Self {
+        Lcg {
+            state,
+            multiplier,
+            increment,
+        }
+    }
+
+    /// Creates a new LCG with the constants used by the Pokémon games
+    #[must_use]
+    pub fn pokemon(state: u32) -> Self {
+        Lcg::new(state, Self::POKEMON_MULTIPLIER, Self::POKEMON_INCREMENT)
+    }
+}
+
+impl Default for Lcg {
+    fn default() -> Self {
+        Lcg::pokemon(0)
+    }
+}
+
+impl RandomSource for Lcg {
+    const NAME: &'static str = "LCG";
+
+    /// Uses the Pokémon constants, starting from the `seed`
+    fn from_seed(seed: Seed) -> Self {
+        Lcg::pokemon(seed.0)
+    }
+
+    fn step(&mut self) {
+        self.state = self
+            .state
+            .wrapping_mul(self.multiplier)
+            .wrapping_add(self.increment);
+    }
+
+    fn internals(&self) -> RNGInternals {
+        let [s, t, _, _] = self.state.to_be_bytes();
+
+        RNGInternals { s, t, result: s }
+    }
+}
+
+/// A 16-bit linear feedback shift register, in the style of the NES-era generators
+///
+/// Each step shifts the state right by one bit, feeding back the XOR of bits 0, 2, 3 and 5.
+/// This visits all 65535 non-zero states before repeating.
+///
+/// The result is the low byte of the state: `s` is the high byte and `t` is the low byte.
+/// Because only a single bit changes position per step,
+/// consecutive results are strongly correlated and very easy to manipulate.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lfsr {
+    state: u16,
+}
+
+impl Lfsr {
+    /// Creates a new LFSR with the given internal state
+    ///
+    /// A state of 0 is a fixed point of the register, and is replaced by 1.
+    #[must_use]
+    pub fn new(state: u16) -> Self {
+        Lfsr {
+            state: state.max(1),
+        }
+    }
+}
+
+impl Default for Lfsr {
+    fn default() -> Self {
+        Lfsr::new(0xACE1)
+    }
+}
+
+impl RandomSource for Lfsr {
+    const NAME: &'static str = "LFSR";
+
+    /// Uses the two lowest bytes of the `seed` as the state, mixed with the two highest bytes
+    fn from_seed(seed: Seed) -> Self {
+        Lfsr::new((seed.0 >> 16) as u16 ^ seed.0 as u16)
+    }
+
+    fn step(&mut self) {
+        let bit = (self.state ^ (self.state >> 2) ^ (self.state >> 3) ^ (self.state >> 5)) & 1;
+        self.state = (self.state >> 1) | (bit << 15);
+    }
+
+    fn internals(&self) -> RNGInternals {
+        let [s, t] = self.state.to_be_bytes();
+
+        RNGInternals { s, t, result: t }
+    }
+}
+
+/// A generator that walks through a fixed table of 256 values, like the one used in Final Fantasy
+///
+/// Each step advances the index into the table by one, wrapping around after 256 steps.
+/// `s` is the current index into the table and `t` is always 0.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LookupTable {
+    table: [u8; 256],
+    index: u8,
+}
+
+impl LookupTable {
+    /// The table used by [`LookupTable::default`]: every `u8` exactly once, in a fixed scrambled order
+    pub const DEFAULT_TABLE: [u8; 256] = shuffled_table();
+
+    /// Creates a new generator that reads from the provided `table`, starting at `index`
+    #[must_use]
+    pub fn new(table: [u8; 256], index: u8) -> Self {
+        LookupTable { table, index }
+    }
+}
+
+impl Default for LookupTable {
+    fn default() -> Self {
+        LookupTable::new(Self::DEFAULT_TABLE, 0)
+    }
+}
+
+impl RandomSource for LookupTable {
+    const NAME: &'static str = "Table";
+
+    /// Uses the [`LookupTable::DEFAULT_TABLE`], starting at every byte of the `seed` mixed together
+    fn from_seed(seed: Seed) -> Self {
+        let index = seed
+            .0
+            .to_be_bytes()
+            .iter()
+            .fold(0, |index, byte| index ^ byte);
+
+        LookupTable::new(Self::DEFAULT_TABLE, index)
+    }
+
+    fn step(&mut self) {
+        self.index = self.index.wrapping_add(1);
+    }
+
+    fn internals(&self) -> RNGInternals {
+        RNGInternals {
+            s: self.index,
+            t: 0,
+            result: self.table[self.index as usize],
+        }
+    }
+}
+
+/// Shuffles the values 0..=255 using a xorshift generator with a fixed seed
+const fn shuffled_table() -> [u8; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        table[i] = i as u8;
+        i += 1;
+    }
+
+    let mut seed: u32 = 0x2545_F491;
+    let mut i = 255;
+    while i > 0 {
+        seed ^= seed << 13;
+        seed ^= seed >> 17;
+        seed ^= seed << 5;
+
+        let j = (seed % (i as u32 + 1)) as usize;
+        let swap = table[i];
+        table[i] = table[j];
+        table[j] = swap;
+        i -= 1;
+    }
+
+    table
+}
+
+/// Any of the generators provided by this crate, selectable at runtime
+///
+/// Use this with [`RNGPlugin`](super::RNGPlugin) when the generator should depend on the game mode or dungeon,
+/// rather than being fixed at compile time.
+#[derive(Debug, Clone, PartialEq)]
+#[allow(missing_docs)]
+pub enum AnyRng {
+    Smw(Rng),
+    Lcg(Lcg),
+    Lfsr(Lfsr),
+    LookupTable(LookupTable),
+}
+
+impl AnyRng {
+    /// The [`RandomSource::NAME`] of the wrapped generator
+    #[must_use]
+    pub fn name(&self) -> &'static str {
+        match self {
+            AnyRng::Smw(_) => Rng::NAME,
+            AnyRng::Lcg(_) => Lcg::NAME,
+            AnyRng::Lfsr(_) => Lfsr::NAME,
+            AnyRng::LookupTable(_) => LookupTable::NAME,
+        }
+    }
+}
+
+impl Default for AnyRng {
+    fn default() -> Self {
+        AnyRng::Smw(Rng::default())
+    }
+}
+
+impl RandomSource for AnyRng {
+    const NAME: &'static str = "Any";
+
+    /// Uses the SMW-style [`Rng`], as it is the default generator of the game
+    fn from_seed(seed: Seed) -> Self {
+        AnyRng::Smw(Rng::from_seed(seed))
+    }
+
+    fn step(&mut self) {
+        match self {
+            AnyRng::Smw(rng) => rng.step(),
+            AnyRng::Lcg(rng) => rng.step(),
+            AnyRng::Lfsr(rng) => rng.step(),
+            AnyRng::LookupTable(rng) => rng.step(),
+        }
+    }
+
+    fn internals(&self) -> RNGInternals {
+        match self {
+            AnyRng::Smw(rng) => rng.internals(),
+            AnyRng::Lcg(rng) => rng.internals(),
+            AnyRng::Lfsr(rng) => rng.internals(),
+            AnyRng::LookupTable(rng) => rng.internals(),
+        }
+    }
+}
+
+impl From<Rng> for AnyRng {
+    fn from(rng: Rng) -> Self {
+        AnyRng::Smw(rng)
+    }
+}
+
+impl From<Lcg> for AnyRng {
+    fn from(rng: Lcg) -> Self {
+        AnyRng::Lcg(rng)
+    }
+}
+
+impl From<Lfsr> for AnyRng {
+    fn from(rng: Lfsr) -> Self {
+        AnyRng::Lfsr(rng)
+    }
+}
+
+impl From<LookupTable> for AnyRng {
+    fn from(rng: LookupTable) -> Self {
+        AnyRng::LookupTable(rng)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEED: Seed = Seed(0x1234_5678);
+
+    fn first_results(mut rng: impl RandomSource, count: usize) -> Vec<u8> {
+        (0..count).map(|_| rng.gen()).collect()
+    }
+
+    #[test]
+    fn lcg_matches_known_outputs() {
+        assert_eq!(first_results(Lcg::default(), 5), [0, 233, 82, 49, 142]);
+        assert_eq!(
+            first_results(Lcg::from_seed(SEED), 5),
+            [11, 132, 217, 244, 38]
+        );
+    }
+
+    #[test]
+    fn lfsr_matches_known_outputs() {
+        assert_eq!(first_results(Lfsr::default(), 5), [112, 56, 156, 206, 103]);
+        assert_eq!(
+            first_results(Lfsr::from_seed(SEED), 5),
+            [38, 19, 137, 68, 34]
+        );
+    }
+
+    #[test]
+    fn lfsr_never_starts_at_zero() {
+        assert_eq!(Lfsr::new(0), Lfsr::new(1));
+        assert_eq!(Lfsr::from_seed(Seed(0x0001_0001)), Lfsr::new(1));
+    }
+
+    #[test]
+    fn lookup_table_matches_known_outputs() {
+        assert_eq!(
+            first_results(LookupTable::default(), 5),
+            [69, 158, 108, 188, 53]
+        );
+        assert_eq!(
+            first_results(LookupTable::from_seed(SEED), 5),
+            [216, 62, 253, 220, 250]
+        );
+    }
+
+    #[test]
+    fn default_table_contains_every_value_once() {
+        let mut table = LookupTable::DEFAULT_TABLE;
+        table.sort_unstable();
+
+        assert!(table.iter().copied().eq(0..=255));
+    }
+
+    #[test]
+    fn seeding_is_deterministic() {
+        fn check<R: RandomSource>() {
+            assert_eq!(
+                first_results(R::from_seed(SEED), 20),
+                first_results(R::from_seed(SEED), 20),
+                "{} should produce the same values from the same seed",
+                R::NAME
+            );
+        }
+
+        check::<Rng>();
+        check::<Lcg>();
+        check::<Lfsr>();
+        check::<LookupTable>();
+        check::<AnyRng>();
+    }
+
+    #[test]
+    fn any_rng_forwards_to_the_wrapped_generator() {
+        fn check(rng: impl RandomSource + Into<AnyRng>) {
+            let mut wrapped: AnyRng = rng.clone().into();
+            let mut rng = rng;
+
+            for _ in 0..20 {
+                assert_eq!(wrapped.gen_verbose(), rng.gen_verbose());
+            }
+        }
+
+        check(Rng::from_seed(SEED));
+        check(Lcg::from_seed(SEED));
+        check(Lfsr::from_seed(SEED));
+        check(LookupTable::from_seed(SEED));
+    }
+
+    #[test]
+    fn any_rng_reports_the_wrapped_name() {
+        assert_eq!(AnyRng::default().name(), Rng::NAME);
+        assert_eq!(AnyRng::from(Lcg::default()).name(), Lcg::NAME);
+        assert_eq!(AnyRng::from(Lfsr::default()).name(), Lfsr::NAME);
+        assert_eq!(
+            AnyRng::from(LookupTable::default()).name(),
+            LookupTable::NAME
+        );
+        assert_eq!(AnyRng::from_seed(SEED), AnyRng::Smw(Rng::from_seed(SEED)));
+    }
+}
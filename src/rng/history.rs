@@ -0,0 +1,223 @@
+//! A record of every RNG value that has been used, and what it was used for
+
+use bevy::prelude::*;
+use std::collections::VecDeque;
+use std::fmt::Display;
+
+/// The kind of roll that an RNG value was used for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[allow(missing_docs)]
+pub enum RollKind {
+    Hit,
+    Dodge,
+    Damage,
+    Crit,
+    Heal,
+    Flee,
+    SpellFail,
+    Check,
+    Choice,
+    Initiative,
+    Status,
+    Mana,
+    Polymorph,
+    CoinFlip,
+    Other,
+}
+
+impl Display for RollKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            RollKind::Hit => "hit",
+            RollKind::Dodge => "dodge",
+            RollKind::Damage => "damage",
+            RollKind::Crit => "crit",
+            RollKind::Heal => "heal",
+            RollKind::Flee => "flee",
+            RollKind::SpellFail => "spell failure",
+            RollKind::Check => "check",
+            RollKind::Choice => "choice",
+            RollKind::Initiative => "initiative",
+            RollKind::Status => "status effect",
+            RollKind::Mana => "mana",
+            RollKind::Polymorph => "polymorph",
+            RollKind::CoinFlip => "coin flip",
+            RollKind::Other => "other",
+        })
+    }
+}
+
+/// Who used an RNG value, and why
+#[derive(Debug, Clone, PartialEq)]
+pub struct DrawLabel {
+    /// The creature whose roll this is
+    pub actor: Entity,
+    /// The name of the action that the roll is part of
+    pub action: String,
+    /// What the roll determines
+    pub roll: RollKind,
+}
+
+impl DrawLabel {
+    /// Creates a new [`DrawLabel`]
+    pub fn new(actor: Entity, action: impl Into<String>, roll: RollKind) -> Self {
+        DrawLabel {
+            actor,
+            action: action.into(),
+            roll,
+        }
+    }
+}
+
+/// A single value that was drawn from the RNG
+#[derive(Debug, Clone, PartialEq)]
+pub struct RngDraw {
+    /// The position of the value in the sequence of all RNG values
+    ///
+    /// See [`RNGOutputs::index`](super::RNGOutputs::index).
+    pub index: usize,
+    /// The value that was drawn
+    pub value: u8,
+    /// Who drew the value, and why
+    pub label: DrawLabel,
+}
+
+impl RngDraw {
+    /// Describes this draw to the player, using the provided name for the actor
+    #[must_use]
+    pub fn describe(&self, actor_name: &str) -> String {
+        format!(
+            "Using {:03} to determine {actor_name}'s {} ({})",
+            self.value, self.label.action, self.label.roll
+        )
+    }
+}
+
+/// The most recent values that have been drawn from the RNG, in the order they were drawn, stored as a resource
+///
+/// Values are added by [`get_next_rng_value`](super::get_next_rng_value).
+/// Only the last [`RngLog::CAPACITY`] draws are kept.
+#[derive(Debug, Clone, Default)]
+pub struct RngLog {
+    draws: VecDeque<RngDraw>,
+}
+
+impl RngLog {
+    /// The number of draws kept before the oldest are dropped
+    pub const CAPACITY: usize = 1024;
+
+    /// Adds a new draw to the end of the log, dropping the oldest draw if the log is full
+    pub fn record(&mut self, draw: RngDraw) {
+        self.draws.push_back(draw);
+
+        if self.draws.len() > Self::CAPACITY {
+            self.draws.pop_front();
+        }
+    }
+
+    /// Iterates over all draws, from oldest to newest
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &RngDraw> {
+        self.draws.iter()
+    }
+
+    /// The most recent draw
+    #[must_use]
+    pub fn last(&self) -> Option<&RngDraw> {
+        self.draws.back()
+    }
+
+    /// The total number of draws
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.draws.len()
+    }
+
+    /// Have any values been drawn?
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.draws.is_empty()
+    }
+
+    /// Iterates over the draws made by the `actor`, from oldest to newest
+    pub fn by_actor(&self, actor: Entity) -> impl DoubleEndedIterator<Item = &RngDraw> {
+        self.draws
+            .iter()
+            .filter(move |draw| draw.label.actor == actor)
+    }
+
+    /// Iterates over the draws made as part of the action named `action`, from oldest to newest
+    pub fn by_action<'a>(
+        &'a self,
+        action: &'a str,
+    ) -> impl DoubleEndedIterator<Item = &'a RngDraw> {
+        self.draws
+            .iter()
+            .filter(move |draw| draw.label.action == action)
+    }
+
+    /// Iterates over the draws made for rolls of the provided `kind`, from oldest to newest
+    pub fn by_roll(&self, kind: RollKind) -> impl DoubleEndedIterator<Item = &RngDraw> {
+        self.draws
+            .iter()
+            .filter(move |draw| draw.label.roll == kind)
+    }
+
+    /// Iterates over every draw made since the draw at the provided position in the sequence of RNG values
+    pub fn since(&self, index: usize) -> impl DoubleEndedIterator<Item = &RngDraw> {
+        self.draws.iter().filter(move |draw| draw.index >= index)
+    }
+
+    /// Removes every draw at or after the provided position in the sequence of RNG values
+    ///
+    /// Used when the RNG is rewound or moved, as those values have not been drawn yet.
+    pub fn forget_from(&mut self, index: usize) {
+        self.draws.retain(|draw| draw.index < index);
+    }
+
+    /// Removes all draws from the log
+    pub fn clear(&mut self) {
+        self.draws.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn draw(index: usize) -> RngDraw {
+        RngDraw {
+            index,
+            value: index as u8,
+            label: DrawLabel::new(Entity::from_raw(0), "Test", RollKind::Other),
+        }
+    }
+
+    #[test]
+    fn oldest_draws_are_dropped_when_full() {
+        let mut log = RngLog::default();
+        for index in 0..RngLog::CAPACITY + 10 {
+            log.record(draw(index));
+        }
+
+        assert_eq!(log.len(), RngLog::CAPACITY);
+        assert_eq!(log.iter().next().map(|draw| draw.index), Some(10));
+        assert_eq!(
+            log.last().map(|draw| draw.index),
+            Some(RngLog::CAPACITY + 9)
+        );
+    }
+
+    #[test]
+    fn forgetting_removes_later_draws() {
+        let mut log = RngLog::default();
+        for index in 0..10 {
+            log.record(draw(index));
+        }
+
+        log.forget_from(6);
+
+        assert_eq!(log.len(), 6);
+        assert_eq!(log.last().map(|draw| draw.index), Some(5));
+        assert_eq!(log.since(6).count(), 0);
+    }
+}
@@ -0,0 +1,546 @@
+//! Generates random numbers and stores them as resources
+
+use std::collections::VecDeque;
+use std::fmt::Display;
+use std::marker::PhantomData;
+
+use bevy::prelude::*;
+use leafwing_terminal::*;
+
+pub mod analysis;
+
+mod generators;
+pub use generators::*;
+
+mod history;
+pub use history::*;
+
+mod roll;
+pub use roll::*;
+
+mod seed;
+pub use seed::*;
+
+/// Initializes resources for RNG state and values
+///
+/// The generator used is controlled by the type parameter `R`,
+/// which defaults to the SMW-style [`Rng`].
+/// It is created from the [`Seed`] resource, which is inserted with its default value if it does not already exist.
+pub struct RNGPlugin<R: RandomSource = Rng> {
+    _phantom: PhantomData<R>,
+}
+
+impl<R: RandomSource> Default for RNGPlugin<R> {
+    fn default() -> Self {
+        RNGPlugin {
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<R: RandomSource> Plugin for RNGPlugin<R> {
+    fn build(&self, app: &mut App) {
+        let seed = *app.world.get_resource_or_insert_with(Seed::default);
+
+        app.insert_resource(R::from_seed(seed))
+            .init_resource::<RngWindow>()
+            .init_resource::<RngLog>()
+            .init_resource::<RNGOutputs<R>>()
+            .add_system_to_stage(CoreStage::PreUpdate, sync_rng_window::<R>);
+    }
+}
+
+/// A deterministic generator of `u8` values, which can be stored as a resource
+///
+/// Implement this trait to make a new generator available to [`RNGPlugin`] and [`RNGOutputs`].
+pub trait RandomSource: Clone + Send + Sync + 'static {
+    /// A short, human-readable name for this family of generators
+    const NAME: &'static str;
+
+    /// Creates a new generator whose internal state is fully determined by the `seed`
+    #[must_use]
+    fn from_seed(seed: Seed) -> Self;
+
+    /// Advances the internal state of the generator once
+    fn step(&mut self);
+
+    /// The internal state of the generator, and the value that this state produces
+    #[must_use]
+    fn internals(&self) -> RNGInternals;
+
+    /// Generates a new random number between 0 and 255
+    #[must_use]
+    fn gen(&mut self) -> u8 {
+        self.gen_verbose().result
+    }
+
+    /// Generates a new random number between 0 and 255, also outputting the internal RNG state that led to that result
+    fn gen_verbose(&mut self) -> RNGInternals {
+        self.step();
+        self.internals()
+    }
+}
+
+/// A dead simple RNG function that generates a sequence of u8 values
+/// Most states repeat after 32512 steps: see [`analysis`] for the full structure of the state space
+///
+/// Based on the SMW RNG function
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rng {
+    s: u8,
+    t: u8,
+}
+
+impl Rng {
+    /// Create a new RNG with the given internal state
+    #[must_use]
+    pub fn new(s: u8, t: u8) -> Self {
+        Rng { s, t }
+    }
+
+    fn get_bit_at(input: u8, n: u8) -> bool {
+        if n < 8 {
+            input & (1 << n) != 0
+        } else {
+            false
+        }
+    }
+}
+
+impl RandomSource for Rng {
+    const NAME: &'static str = "SMW";
+
+    /// Uses the two lowest bytes of the `seed` as `s` and `t`, mixed with the two highest bytes
+    ///
+    /// Seeds whose highest bytes are 0, such as the default seed, use their lowest bytes unchanged.
+    fn from_seed(seed: Seed) -> Self {
+        let [a, b, s, t] = seed.0.to_be_bytes();
+
+        Rng::new(s ^ a, t ^ b)
+    }
+
+    /// Advances the RNG once
+    fn step(&mut self) {
+        self.s = self.s.wrapping_mul(5).wrapping_add(1);
+        self.t = self.t.wrapping_mul(2);
+
+        if Self::get_bit_at(self.t, 4) == Self::get_bit_at(self.t, 7) {
+            self.t = self.t.wrapping_add(1);
+        }
+    }
+
+    fn internals(&self) -> RNGInternals {
+        RNGInternals {
+            s: self.s,
+            t: self.t,
+            result: self.s ^ self.t,
+        }
+    }
+}
+
+impl Default for Rng {
+    fn default() -> Self {
+        // In general, it is bad to always initialize your RNG function to the same value
+        // But this isn't supposed to be a good RNG function >:3
+        Self::new(42, 69)
+    }
+}
+
+/// A struct that bundles an RNG result with the two internal RNG state values that generated it.
+///
+/// For the SMW-style [`Rng`], `result` will always be equal to `s ^ t`.
+/// Other generators report the two bytes of their state that are most relevant to the result:
+/// see the documentation of each [`RandomSource`] for details.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RNGInternals {
+    /// First internal value of the RNG function
+    pub s: u8,
+    /// Second internal value of the RNG function
+    pub t: u8,
+    /// The random value generated by the RNG function call
+    pub result: u8,
+}
+
+impl Display for RNGInternals {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "{:03}, {:03}: {:03}",
+            self.s, self.t, self.result
+        ))
+    }
+}
+
+/// How many RNG values the player can see on either side of the "up next" value
+///
+/// Change this resource to grow or shrink the window during play:
+/// [`RNGOutputs`] is updated to match at the start of the next frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RngWindow {
+    /// The number of previously used values that are kept
+    pub lookbehind: usize,
+    /// The number of values after the "up next" value that are generated ahead of time
+    pub lookahead: usize,
+}
+
+impl Default for RngWindow {
+    fn default() -> Self {
+        RngWindow {
+            lookbehind: 10,
+            lookahead: 10,
+        }
+    }
+}
+
+/// Ring buffer of current, past, and upcoming RNG values, produced by the generator `R`
+///
+/// The number of values stored on either side of the "up next" value is controlled by its [`RngWindow`].
+///
+/// The state of the generator before any values were produced is stored,
+/// allowing the buffer to be rewound or moved to any position in the sequence of RNG values.
+#[derive(Clone)]
+pub struct RNGOutputs<R: RandomSource = Rng> {
+    buffer: VecDeque<RNGInternals>,
+    window: RngWindow,
+    origin: R,
+    index: usize,
+}
+
+impl<R: RandomSource> RNGOutputs<R> {
+    /// Creates a new buffer of values, produced by repeatedly stepping the `rng`
+    ///
+    /// The state of the `rng` before this method was called is used as the origin of the sequence.
+    /// The first values of the sequence fill the lookbehind, so that the buffer starts out full.
+    pub fn new(rng: &mut R, window: RngWindow) -> Self {
+        let mut outputs = RNGOutputs {
+            buffer: VecDeque::new(),
+            window,
+            origin: rng.clone(),
+            index: 0,
+        };
+
+        outputs.seek(rng, window.lookbehind);
+        outputs
+    }
+
+    /// The "up next" RNG value, and the internal state that produced it
+    #[must_use]
+    pub fn up_next(&self) -> RNGInternals {
+        self.buffer[self.up_next_position()]
+    }
+
+    /// The position of the "up next" value within [`RNGOutputs::iter`]
+    ///
+    /// This is equal to the number of previous values stored,
+    /// which is less than the lookbehind of the [`RngWindow`] until enough values have been used.
+    #[must_use]
+    pub fn up_next_position(&self) -> usize {
+        self.window.lookbehind.min(self.index)
+    }
+
+    /// Iterates over the stored values, from oldest to newest
+    pub fn iter(&self) -> impl Iterator<Item = &RNGInternals> {
+        self.buffer.iter()
+    }
+
+    /// Iterates over the previously used values that are still stored, from oldest to newest
+    pub fn previous(&self) -> impl Iterator<Item = &RNGInternals> {
+        self.buffer.iter().take(self.up_next_position())
+    }
+
+    /// Iterates over the "up next" value, followed by each of the lookahead values
+    pub fn upcoming(&self) -> impl Iterator<Item = &RNGInternals> {
+        self.buffer.iter().skip(self.up_next_position())
+    }
+
+    /// The current lookbehind and lookahead of the buffer
+    #[must_use]
+    pub fn window(&self) -> RngWindow {
+        self.window
+    }
+
+    /// Resizes the buffer to match the provided `window`, updating the `rng` to match
+    pub fn set_window(&mut self, rng: &mut R, window: RngWindow) {
+        self.window = window;
+        self.seek(rng, self.index);
+    }
+
+    /// The position of the "up next" RNG value in the sequence of all values produced since the origin
+    ///
+    /// This starts at the lookbehind of the [`RngWindow`], and increases by one each time a value is used.
+    #[must_use]
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Moves the buffer so that the "up next" value is the one at the provided `index`, updating the `rng` to match
+    pub fn seek(&mut self, rng: &mut R, index: usize) {
+        let first = index.saturating_sub(self.window.lookbehind);
+        let mut generator = self.origin.clone();
+
+        // The value at index `i` is produced by the `i + 1`th step from the origin
+        for _ in 0..first {
+            generator.step();
+        }
+
+        self.buffer.clear();
+        for _ in first..=index + self.window.lookahead {
+            self.buffer.push_back(generator.gen_verbose());
+        }
+
+        *rng = generator;
+        self.index = index;
+    }
+
+    /// Moves the buffer back by `steps`, so that previously used values will be used again
+    ///
+    /// The buffer cannot be rewound past the origin.
+    pub fn rewind(&mut self, rng: &mut R, steps: usize) {
+        self.seek(rng, self.index.saturating_sub(steps));
+    }
+}
+
+impl<R: RandomSource> FromWorld for RNGOutputs<R> {
+    fn from_world(world: &mut World) -> Self {
+        let window = *world.get_resource_or_insert_with(RngWindow::default);
+        let mut rng = world.get_resource_mut::<R>().unwrap();
+
+        RNGOutputs::new(&mut *rng, window)
+    }
+}
+
+/// Gets the "up next" RNG value from the ring buffer, then generates a new value to replace it
+///
+/// The value is recorded in the [`RngLog`] alongside the provided `label`, describing who used it and why.
+pub fn get_next_rng_value<R: RandomSource>(
+    rng: &mut R,
+    buffer: &mut RNGOutputs<R>,
+    log: &mut RngLog,
+    label: DrawLabel,
+) -> u8 {
+    let val = buffer.up_next().result;
+    log.record(RngDraw {
+        index: buffer.index,
+        value: val,
+        label,
+    });
+
+    buffer.buffer.push_back(rng.gen_verbose());
+    buffer.index += 1;
+
+    // Only drop the oldest value once the lookbehind is full
+    if buffer.index > buffer.window.lookbehind {
+        buffer.buffer.pop_front();
+    }
+
+    val
+}
+
+/// Resizes the [`RNGOutputs`] whenever the [`RngWindow`] is changed
+fn sync_rng_window<R: RandomSource>(
+    window: Res<RngWindow>,
+    mut rng: ResMut<R>,
+    mut rng_outputs: ResMut<RNGOutputs<R>>,
+) {
+    if window.is_changed() && *window != rng_outputs.window() {
+        rng_outputs.set_window(&mut *rng, *window);
+    }
+}
+
+/// Adds the `rewind` and `seek` terminal commands, which move the RNG freely through time
+///
+/// These are intended for debugging, as they ignore the usual rules of the game.
+/// The type parameter `R` must match the generator used by the [`RNGPlugin`].
+pub struct TimeTravelPlugin<R: RandomSource = Rng> {
+    _phantom: PhantomData<R>,
+}
+
+impl<R: RandomSource> Default for TimeTravelPlugin<R> {
+    fn default() -> Self {
+        TimeTravelPlugin {
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<R: RandomSource> Plugin for TimeTravelPlugin<R> {
+    fn build(&self, app: &mut App) {
+        app.add_terminal_command::<RewindCommand, _, _>(rewind_command::<R>)
+            .add_terminal_command::<SeekCommand, _, _>(seek_command::<R>);
+    }
+}
+
+/// The furthest past the current index that the `seek` command can move the RNG
+///
+/// Seeking replays the sequence from its origin, so it takes longer the further along the index is.
+pub const MAX_SEEK_DISTANCE: usize = 1 << 16;
+
+/// Moves the RNG back, so that previously used values will be used again
+#[derive(TerminalCommand)]
+#[terminal_command(name = "rewind")]
+struct RewindCommand {
+    /// The number of RNG values to step back by
+    steps: i64,
+}
+
+fn rewind_command<R: RandomSource>(
+    mut terminal_command: TerminalCommand<RewindCommand>,
+    mut rng: ResMut<R>,
+    mut rng_outputs: ResMut<RNGOutputs<R>>,
+    mut rng_log: ResMut<RngLog>,
+) {
+    if let Some(RewindCommand { steps }) = terminal_command.take() {
+        match usize::try_from(steps) {
+            Ok(steps) => {
+                rng_outputs.rewind(&mut *rng, steps);
+                rng_log.forget_from(rng_outputs.index());
+                terminal_command.reply(format!("The RNG is now at index {}.", rng_outputs.index()));
+            }
+            Err(_) => terminal_command
+                .reply_failed("The RNG can only be rewound by a positive number of steps."),
+        }
+    }
+}
+
+/// Moves the RNG to any position in its sequence
+#[derive(TerminalCommand)]
+#[terminal_command(name = "seek")]
+struct SeekCommand {
+    /// The index of the RNG value that should be up next
+    index: i64,
+}
+
+fn seek_command<R: RandomSource>(
+    mut terminal_command: TerminalCommand<SeekCommand>,
+    mut rng: ResMut<R>,
+    mut rng_outputs: ResMut<RNGOutputs<R>>,
+    mut rng_log: ResMut<RngLog>,
+) {
+    if let Some(SeekCommand { index }) = terminal_command.take() {
+        match seek_index(rng_outputs.index(), index) {
+            Ok(index) => {
+                rng_outputs.seek(&mut *rng, index);
+                rng_log.forget_from(index);
+                terminal_command.reply(format!("The RNG is now at index {}.", rng_outputs.index()));
+            }
+            Err(error) => terminal_command.reply_failed(error),
+        }
+    }
+}
+
+/// Checks that the `seek` command can move the RNG from the `current` index to the `requested` one
+fn seek_index(current: usize, requested: i64) -> Result<usize, String> {
+    let requested =
+        usize::try_from(requested).map_err(|_| "RNG indexes cannot be negative.".to_string())?;
+    let furthest = current.saturating_add(MAX_SEEK_DISTANCE);
+
+    if requested > furthest {
+        Err(format!(
+            "The RNG can only seek up to {MAX_SEEK_DISTANCE} values ahead, to index {furthest}."
+        ))
+    } else {
+        Ok(requested)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn draw(rng: &mut Rng, outputs: &mut RNGOutputs, log: &mut RngLog) -> u8 {
+        let label = DrawLabel::new(Entity::from_raw(0), "Test", RollKind::Other);
+        get_next_rng_value(rng, outputs, log, label)
+    }
+
+    #[test]
+    fn every_byte_of_the_seed_changes_the_rng() {
+        let default = Rng::from_seed(Seed::default());
+
+        assert_eq!(default, Rng::default());
+        for shift in [0, 8, 16, 24] {
+            let seed = Seed(Seed::default().0 ^ (1 << shift));
+            assert_ne!(Rng::from_seed(seed), default);
+        }
+    }
+
+    #[test]
+    fn seeking_is_limited_to_nearby_indexes() {
+        assert_eq!(seek_index(100, 0), Ok(0));
+        assert_eq!(
+            seek_index(100, 100 + MAX_SEEK_DISTANCE as i64),
+            Ok(100 + MAX_SEEK_DISTANCE)
+        );
+        assert!(seek_index(100, 101 + MAX_SEEK_DISTANCE as i64).is_err());
+        assert!(seek_index(0, i64::MAX).is_err());
+        assert!(seek_index(100, -1).is_err());
+    }
+
+    #[test]
+    fn seek_matches_stepping_from_the_origin() {
+        let mut rng = Rng::default();
+        let mut generator = rng.clone();
+        let mut outputs = RNGOutputs::new(&mut rng, RngWindow::default());
+
+        outputs.seek(&mut rng, 100);
+        let expected = (0..=100).map(|_| generator.gen()).last();
+
+        assert_eq!(outputs.index(), 100);
+        assert_eq!(Some(outputs.up_next().result), expected);
+    }
+
+    #[test]
+    fn rewinding_repeats_the_same_values() {
+        let mut rng = Rng::default();
+        let mut log = RngLog::default();
+        let mut outputs = RNGOutputs::new(&mut rng, RngWindow::default());
+        outputs.seek(&mut rng, 20);
+
+        let first: Vec<u8> = (0..5)
+            .map(|_| draw(&mut rng, &mut outputs, &mut log))
+            .collect();
+        outputs.rewind(&mut rng, 5);
+        let second: Vec<u8> = (0..5)
+            .map(|_| draw(&mut rng, &mut outputs, &mut log))
+            .collect();
+
+        assert_eq!(first, second);
+        assert_eq!(outputs.index(), 25);
+    }
+
+    #[test]
+    fn seeking_back_and_forth_restores_the_buffer() {
+        let mut rng = Rng::default();
+        let mut outputs = RNGOutputs::new(&mut rng, RngWindow::default());
+        outputs.seek(&mut rng, 50);
+        let buffer: Vec<RNGInternals> = outputs.iter().copied().collect();
+        let state = rng.clone();
+
+        outputs.seek(&mut rng, 5000);
+        outputs.seek(&mut rng, 50);
+
+        assert_eq!(outputs.iter().copied().collect::<Vec<_>>(), buffer);
+        assert_eq!(rng, state);
+    }
+
+    #[test]
+    fn new_buffers_start_with_a_full_lookbehind() {
+        let mut rng = Rng::default();
+        let window = RngWindow::default();
+        let outputs = RNGOutputs::new(&mut rng, window);
+
+        assert_eq!(outputs.index(), window.lookbehind);
+        assert_eq!(outputs.previous().count(), window.lookbehind);
+        assert_eq!(outputs.upcoming().count(), window.lookahead + 1);
+    }
+
+    #[test]
+    fn rewinding_stops_at_the_origin() {
+        let mut rng = Rng::default();
+        let mut outputs = RNGOutputs::new(&mut rng, RngWindow::default());
+        outputs.seek(&mut rng, 3);
+
+        outputs.rewind(&mut rng, 10);
+
+        assert_eq!(outputs.index(), 0);
+        assert_eq!(outputs.previous().count(), 0);
+    }
+}
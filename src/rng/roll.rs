@@ -0,0 +1,270 @@
+//! Rolls that use several RNG values at once, such as those made with advantage or disadvantage
+
+use super::{get_next_rng_value, DrawLabel, RNGOutputs, RandomSource, RngLog, RollKind};
+use bevy::prelude::*;
+use std::fmt::Display;
+
+/// How the RNG values used by a single roll are combined into one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Combine {
+    /// The value that goes the roller's way the most
+    Best,
+    /// The value that goes against the roller the most
+    Worst,
+    /// The total of the values, capped at the largest possible value
+    Sum,
+    /// The mean of the values, rounded down
+    Average,
+}
+
+impl Display for Combine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Combine::Best => "best",
+            Combine::Worst => "worst",
+            Combine::Sum => "sum",
+            Combine::Average => "average",
+        })
+    }
+}
+
+/// How many RNG values a roll uses, and how they are combined
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RollMode {
+    /// The number of RNG values used, which is always at least one
+    pub draws: usize,
+    /// How the values are combined
+    pub combine: Combine,
+}
+
+impl Default for RollMode {
+    fn default() -> Self {
+        RollMode::SINGLE
+    }
+}
+
+impl RollMode {
+    /// An ordinary roll, which uses a single RNG value as-is
+    pub const SINGLE: RollMode = RollMode {
+        draws: 1,
+        combine: Combine::Best,
+    };
+
+    /// Creates a new [`RollMode`], which uses at least one RNG value
+    #[must_use]
+    pub fn new(draws: usize, combine: Combine) -> Self {
+        RollMode {
+            draws: draws.max(1),
+            combine,
+        }
+    }
+
+    /// Does this roll use more than one RNG value?
+    #[must_use]
+    pub fn is_multiple(&self) -> bool {
+        self.draws > 1
+    }
+
+    /// Combines the `values` used by a roll of the provided `kind` into a single value
+    ///
+    /// Which value is best depends on the `kind` of roll: see [`RollKind::favors_high`].
+    #[must_use]
+    pub fn combine(&self, values: &[u8], kind: RollKind) -> u8 {
+        let highest = values.iter().copied().max().unwrap_or_default();
+        let lowest = values.iter().copied().min().unwrap_or_default();
+
+        match (self.combine, kind.favors_high()) {
+            (Combine::Best, true) | (Combine::Worst, false) => highest,
+            (Combine::Best, false) | (Combine::Worst, true) => lowest,
+            (Combine::Sum, _) => values
+                .iter()
+                .fold(0u8, |total, value| total.saturating_add(*value)),
+            (Combine::Average, _) => match values.len() {
+                0 => 0,
+                len => (values.iter().map(|value| *value as usize).sum::<usize>() / len) as u8,
+            },
+        }
+    }
+}
+
+impl RollKind {
+    /// Do higher RNG values go the way of the creature making this kind of roll?
+    ///
+    /// Crits and attempts to flee succeed on low values, while every other roll prefers high ones.
+    #[must_use]
+    pub fn favors_high(&self) -> bool {
+        !matches!(self, RollKind::Crit | RollKind::Flee)
+    }
+}
+
+/// Draws the RNG values for a roll made in the provided `mode`, returning each value drawn along with the combined value
+///
+/// Every value is recorded in the [`RngLog`] with the same `label`.
+pub fn draw_roll<R: RandomSource>(
+    rng: &mut R,
+    buffer: &mut RNGOutputs<R>,
+    log: &mut RngLog,
+    label: DrawLabel,
+    mode: RollMode,
+) -> (Vec<u8>, u8) {
+    let kind = label.roll;
+    let values: Vec<u8> = (0..mode.draws)
+        .map(|_| get_next_rng_value(rng, buffer, log, label.clone()))
+        .collect();
+    let combined = mode.combine(&values, kind);
+
+    (values, combined)
+}
+
+/// A single reason that a creature rolls with advantage or disadvantage
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdvantageSource {
+    /// What grants the advantage, which is used to remove it later
+    pub label: String,
+    /// The kind of roll affected, or `None` if every roll is affected
+    pub roll: Option<RollKind>,
+    /// The number of extra RNG values rolled, which is negative for disadvantage
+    pub stacks: i8,
+}
+
+impl AdvantageSource {
+    /// Creates a new [`AdvantageSource`], which affects every roll
+    pub fn new(label: impl Into<String>, stacks: i8) -> Self {
+        AdvantageSource {
+            label: label.into(),
+            roll: None,
+            stacks,
+        }
+    }
+
+    /// Restricts this source to rolls of the provided `kind`
+    #[must_use]
+    pub fn for_roll(mut self, kind: RollKind) -> Self {
+        self.roll = Some(kind);
+        self
+    }
+
+    /// Does this source affect rolls of the provided `kind`?
+    #[must_use]
+    pub fn affects(&self, kind: RollKind) -> bool {
+        self.roll.map_or(true, |roll| roll == kind)
+    }
+}
+
+/// The sources of advantage and disadvantage that a creature has
+///
+/// Sources stack: each stack of advantage rolls one more RNG value and keeps the best,
+/// while each stack of disadvantage rolls one more and keeps the worst.
+/// Advantage and disadvantage cancel out, stack for stack.
+#[derive(Component, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Advantage {
+    sources: Vec<AdvantageSource>,
+}
+
+impl Advantage {
+    /// Adds a new `source` of advantage or disadvantage
+    pub fn add(&mut self, source: AdvantageSource) {
+        self.sources.push(source);
+    }
+
+    /// Removes every source with the provided `label`, returning whether any were removed
+    pub fn remove(&mut self, label: &str) -> bool {
+        let before = self.sources.len();
+        self.sources.retain(|source| source.label != label);
+        self.sources.len() != before
+    }
+
+    /// The sources, in the order that they were added
+    pub fn iter(&self) -> impl Iterator<Item = &AdvantageSource> {
+        self.sources.iter()
+    }
+
+    /// The total stacks of advantage for rolls of the provided `kind`, which is negative for disadvantage
+    #[must_use]
+    pub fn net(&self, kind: RollKind) -> i32 {
+        self.sources
+            .iter()
+            .filter(|source| source.affects(kind))
+            .map(|source| source.stacks as i32)
+            .sum()
+    }
+
+    /// How rolls of the provided `kind` are made
+    #[must_use]
+    pub fn mode(&self, kind: RollKind) -> RollMode {
+        let net = self.net(kind);
+        let combine = if net < 0 {
+            Combine::Worst
+        } else {
+            Combine::Best
+        };
+
+        RollMode::new(1 + net.unsigned_abs() as usize, combine)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn advantage_and_disadvantage_cancel_stack_for_stack() {
+        let mut advantage = Advantage::default();
+        advantage.add(AdvantageSource::new("Blessed", 2));
+        advantage.add(AdvantageSource::new("Cursed", -1));
+        assert_eq!(
+            advantage.mode(RollKind::Hit),
+            RollMode::new(2, Combine::Best)
+        );
+
+        advantage.add(AdvantageSource::new("Hexed", -3));
+        assert_eq!(
+            advantage.mode(RollKind::Hit),
+            RollMode::new(3, Combine::Worst)
+        );
+
+        advantage.add(AdvantageSource::new("Focused", 2).for_roll(RollKind::Crit));
+        assert_eq!(advantage.mode(RollKind::Crit), RollMode::SINGLE);
+        assert_eq!(advantage.net(RollKind::Hit), -2);
+
+        assert!(advantage.remove("Hexed"));
+        assert!(!advantage.remove("Hexed"));
+        assert_eq!(
+            advantage.mode(RollKind::Crit),
+            RollMode::new(4, Combine::Best)
+        );
+    }
+
+    #[test]
+    fn best_and_worst_depend_on_the_kind_of_roll() {
+        let values = [10, 200, 90];
+        let best = RollMode::new(3, Combine::Best);
+        let worst = RollMode::new(3, Combine::Worst);
+
+        assert_eq!(best.combine(&values, RollKind::Hit), 200);
+        assert_eq!(worst.combine(&values, RollKind::Hit), 10);
+
+        // Crits and flees succeed on low values
+        for kind in [RollKind::Crit, RollKind::Flee] {
+            assert_eq!(best.combine(&values, kind), 10);
+            assert_eq!(worst.combine(&values, kind), 200);
+        }
+    }
+
+    #[test]
+    fn sums_saturate_and_averages_round_down() {
+        let sum = RollMode::new(3, Combine::Sum);
+        assert_eq!(sum.combine(&[100, 50, 20], RollKind::Damage), 170);
+        assert_eq!(sum.combine(&[200, 100, 20], RollKind::Damage), u8::MAX);
+
+        let average = RollMode::new(3, Combine::Average);
+        assert_eq!(average.combine(&[255, 255, 255], RollKind::Damage), 255);
+        assert_eq!(average.combine(&[1, 2, 2], RollKind::Damage), 1);
+    }
+
+    #[test]
+    fn rolls_use_at_least_one_value() {
+        assert_eq!(RollMode::new(0, Combine::Worst).draws, 1);
+        assert!(!RollMode::SINGLE.is_multiple());
+    }
+}
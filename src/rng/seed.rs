@@ -0,0 +1,223 @@
+//! Seeds that fully determine the RNG of a run, and can be shared as short strings
+
+use super::{RNGOutputs, RandomSource, Rng, RngLog};
+use crate::GameState;
+use bevy::prelude::*;
+use leafwing_terminal::*;
+use std::fmt::Display;
+use std::marker::PhantomData;
+use std::str::FromStr;
+
+/// Adds the `seed` terminal command, which shows or changes the [`Seed`] of the run
+///
+/// The type parameter `R` must match the generator used by the [`RNGPlugin`](super::RNGPlugin).
+pub struct SeedPlugin<R: RandomSource = Rng> {
+    _phantom: PhantomData<R>,
+}
+
+impl<R: RandomSource> Default for SeedPlugin<R> {
+    fn default() -> Self {
+        SeedPlugin {
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<R: RandomSource> Plugin for SeedPlugin<R> {
+    fn build(&self, app: &mut App) {
+        app.add_terminal_command::<SeedCommand, _, _>(seed_command::<R>);
+    }
+}
+
+/// The value that the RNG of this run was started from, stored as a resource
+///
+/// Seeds are displayed and parsed as short strings using
+/// [Crockford's base 32](https://www.crockford.com/base32.html), so they are easy to read aloud and share.
+/// Parsing is case-insensitive, ignores dashes, and treats the easily-confused `I`, `L` and `O` as `1`, `1` and `0`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Seed(pub u32);
+
+impl Seed {
+    /// The digits used to encode seeds, in order of value
+    const ALPHABET: &'static [u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+    /// The number of characters needed to display any seed
+    pub const LENGTH: usize = 7;
+
+    /// The value of a single character of a seed string, if it is valid
+    fn digit(character: char) -> Option<u32> {
+        match character.to_ascii_uppercase() {
+            'I' | 'L' => Some(1),
+            'O' => Some(0),
+            upper => Self::ALPHABET
+                .iter()
+                .position(|&digit| digit as char == upper)
+                .map(|position| position as u32),
+        }
+    }
+}
+
+impl Default for Seed {
+    fn default() -> Self {
+        // Matches the default state of the SMW-style `Rng`
+        Seed(u32::from_be_bytes([0, 0, 42, 69]))
+    }
+}
+
+impl Display for Seed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut characters = [0; Self::LENGTH];
+        let mut remaining = self.0;
+
+        for character in characters.iter_mut().rev() {
+            *character = Self::ALPHABET[(remaining % 32) as usize];
+            remaining /= 32;
+        }
+
+        // The alphabet is pure ASCII, so this is always valid
+        f.write_str(std::str::from_utf8(&characters).unwrap())
+    }
+}
+
+impl FromStr for Seed {
+    type Err = SeedParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut value: u32 = 0;
+        let mut any_digits = false;
+
+        for character in s.trim().chars().filter(|&c| c != '-') {
+            let digit =
+                Self::digit(character).ok_or(SeedParseError::InvalidCharacter(character))?;
+
+            value = value
+                .checked_mul(32)
+                .and_then(|value| value.checked_add(digit))
+                .ok_or(SeedParseError::TooLarge)?;
+            any_digits = true;
+        }
+
+        if any_digits {
+            Ok(Seed(value))
+        } else {
+            Err(SeedParseError::Empty)
+        }
+    }
+}
+
+/// The ways that parsing a [`Seed`] from a string can fail
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeedParseError {
+    /// The string did not contain any characters
+    Empty,
+    /// The string contained a character that is not part of the seed alphabet
+    InvalidCharacter(char),
+    /// The string encoded a value that does not fit in a seed
+    TooLarge,
+}
+
+impl Display for SeedParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SeedParseError::Empty => f.write_str("seeds cannot be empty"),
+            SeedParseError::InvalidCharacter(character) => {
+                f.write_fmt(format_args!("'{character}' cannot be used in a seed"))
+            }
+            SeedParseError::TooLarge => f.write_str("seed is too large"),
+        }
+    }
+}
+
+impl std::error::Error for SeedParseError {}
+
+/// Shows the seed of the current run
+///
+/// Before the game begins, a new seed can be supplied to change the RNG.
+#[derive(TerminalCommand)]
+#[terminal_command(name = "seed")]
+struct SeedCommand {
+    /// The new seed to use
+    seed: Option<String>,
+}
+
+fn seed_command<R: RandomSource>(
+    mut terminal_command: TerminalCommand<SeedCommand>,
+    game_state: Res<State<GameState>>,
+    mut seed: ResMut<Seed>,
+    mut rng: ResMut<R>,
+    mut rng_outputs: ResMut<RNGOutputs<R>>,
+    mut rng_log: ResMut<RngLog>,
+) {
+    let new_seed = match terminal_command.take() {
+        Some(SeedCommand {
+            seed: Some(new_seed),
+        }) => new_seed,
+        Some(SeedCommand { seed: None }) => {
+            terminal_command.reply(format!("The seed of this run is {}.", *seed));
+            return;
+        }
+        None => return,
+    };
+
+    if *game_state.current() != GameState::GameStart {
+        terminal_command.reply_failed("The seed can only be changed before the game begins.");
+        return;
+    }
+
+    match new_seed.parse::<Seed>() {
+        Ok(new_seed) => {
+            *seed = new_seed;
+            *rng = R::from_seed(new_seed);
+            *rng_outputs = RNGOutputs::new(&mut *rng, rng_outputs.window());
+            rng_log.clear();
+
+            terminal_command.reply_ok(format!("The seed of this run is now {new_seed}."));
+        }
+        Err(error) => terminal_command.reply_failed(format!("Invalid seed: {error}.")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Result<Seed, SeedParseError> {
+        s.parse()
+    }
+
+    #[test]
+    fn display_uses_crockford_base_32() {
+        assert_eq!(Seed::default().to_string(), "0000AJ5");
+        assert_eq!(Seed(0).to_string(), "0000000");
+        assert_eq!(Seed(0x1234_5678).to_string(), "0938NKR");
+        assert_eq!(Seed(u32::MAX).to_string(), "3ZZZZZZ");
+    }
+
+    #[test]
+    fn display_and_parse_round_trip() {
+        for value in [0, 1, 31, 32, 10821, 0x1234_5678, 0x8000_0000, u32::MAX] {
+            let seed = Seed(value);
+            assert_eq!(parse(&seed.to_string()), Ok(seed));
+        }
+    }
+
+    #[test]
+    fn parsing_forgives_case_dashes_and_lookalikes() {
+        assert_eq!(parse("0000aj5"), Ok(Seed::default()));
+        assert_eq!(parse("0000-AJ5"), Ok(Seed::default()));
+        assert_eq!(parse(" AJ5 "), Ok(Seed::default()));
+        assert_eq!(parse("oOoOAJ5"), Ok(Seed::default()));
+        assert_eq!(parse("I"), Ok(Seed(1)));
+        assert_eq!(parse("l"), Ok(Seed(1)));
+    }
+
+    #[test]
+    fn parsing_rejects_invalid_seeds() {
+        assert_eq!(parse(""), Err(SeedParseError::Empty));
+        assert_eq!(parse("--"), Err(SeedParseError::Empty));
+        assert_eq!(parse("AJU"), Err(SeedParseError::InvalidCharacter('U')));
+        assert_eq!(parse("AJ*"), Err(SeedParseError::InvalidCharacter('*')));
+        assert_eq!(parse("4000000"), Err(SeedParseError::TooLarge));
+        assert_eq!(parse("ZZZZZZZZ"), Err(SeedParseError::TooLarge));
+    }
+}
@@ -0,0 +1,372 @@
+//! Applies [`Actions`](Action) to a copy of the combat state, to see what they would do without any side effects
+
+use crate::actions::Action;
+use crate::combat_flow::{Active, Inactive};
+use crate::combat_statistics::{
+    ActionPoints, CreatureResources, CritChance, Damage, DodgeChance, FleeChance, Intelligence,
+    Life, Mana,
+};
+use crate::creatures::{Monster, Player};
+use crate::prediction::Revealed;
+use crate::rng::{Advantage, RNGOutputs, Rng, RngDraw, RngLog};
+use crate::status_effects::{EffectRemoved, StatusEffects};
+use bevy::app::ManualEventReader;
+use bevy::prelude::*;
+use leafwing_terminal::PrintTerminalLine;
+
+/// A copy of the combat state, which actions can be applied to without affecting the real [`World`]
+///
+/// Entities in the simulation have the same ids as in the original world.
+pub struct Simulation {
+    world: World,
+    messages: ManualEventReader<PrintTerminalLine>,
+}
+
+impl Simulation {
+    /// Copies the combat-relevant components and RNG resources out of the `world`
+    ///
+    /// The `world` is only borrowed mutably in order to construct queries: it is not otherwise changed.
+    pub fn snapshot(world: &mut World) -> Self {
+        let mut simulation = Simulation {
+            world: World::new(),
+            messages: ManualEventReader::default(),
+        };
+
+        simulation.copy_components::<Player>(world);
+        simulation.copy_components::<Monster>(world);
+        simulation.copy_components::<Active>(world);
+        simulation.copy_components::<Inactive>(world);
+        simulation.copy_components::<Life>(world);
+        simulation.copy_components::<Mana>(world);
+        simulation.copy_components::<ActionPoints>(world);
+        simulation.copy_components::<Damage>(world);
+        simulation.copy_components::<CritChance>(world);
+        simulation.copy_components::<DodgeChance>(world);
+        simulation.copy_components::<FleeChance>(world);
+        simulation.copy_components::<Intelligence>(world);
+        simulation.copy_components::<Advantage>(world);
+        simulation.copy_components::<StatusEffects>(world);
+        simulation.copy_components::<Revealed>(world);
+        simulation.copy_components::<Name>(world);
+
+        simulation.copy_resource::<Rng>(world);
+        simulation.copy_resource::<RNGOutputs>(world);
+        simulation.copy_resource::<RngLog>(world);
+
+        // Messages are collected into the report, rather than printed
+        simulation
+            .world
+            .insert_resource(Events::<PrintTerminalLine>::default());
+        simulation
+            .world
+            .insert_resource(Events::<EffectRemoved>::default());
+
+        simulation
+    }
+
+    /// Applies every step of the `action` to the simulated world, reporting what happened
+    ///
+    /// The `action` itself is untouched: a fresh copy of it is used.
+    pub fn run(&mut self, action: &Action) -> SimulationReport {
+        let before = self.resources();
+        let index_before = self
+            .world
+            .get_resource::<RNGOutputs>()
+            .map_or(0, RNGOutputs::index);
+
+        let mut action = action.clone();
+        while !action.is_finished() {
+            action.advance(&mut self.world);
+        }
+
+        let after = self.resources();
+        let changes = before
+            .into_iter()
+            .filter_map(|(entity, before)| {
+                let after = after.iter().find(|(other, _)| *other == entity)?.1;
+                (before != after).then(|| CreatureChange {
+                    entity,
+                    before,
+                    after,
+                })
+            })
+            .collect();
+
+        let draws = self
+            .world
+            .get_resource::<RngLog>()
+            .map(|log| log.since(index_before).cloned().collect())
+            .unwrap_or_default();
+
+        let messages = match self.world.get_resource::<Events<PrintTerminalLine>>() {
+            Some(events) => self
+                .messages
+                .iter(events)
+                .map(|message| message.line.clone())
+                .collect(),
+            None => Vec::new(),
+        };
+
+        SimulationReport {
+            draws,
+            changes,
+            messages,
+        }
+    }
+
+    /// The simulated world, including any changes made by [`Simulation::run`]
+    #[must_use]
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    fn copy_components<C: Component + Clone>(&mut self, source: &mut World) {
+        let mut query = source.query::<(Entity, &C)>();
+        let components: Vec<(Entity, C)> = query
+            .iter(source)
+            .map(|(entity, component)| (entity, component.clone()))
+            .collect();
+
+        for (entity, component) in components {
+            // Entities are only ever spawned with ids that do not yet exist in the simulation
+            self.world.get_or_spawn(entity).unwrap().insert(component);
+        }
+    }
+
+    fn copy_resource<R: Clone + Send + Sync + 'static>(&mut self, source: &World) {
+        if let Some(resource) = source.get_resource::<R>() {
+            self.world.insert_resource(resource.clone());
+        }
+    }
+
+    /// The current [`CreatureResources`] of every creature in the simulation
+    fn resources(&mut self) -> Vec<(Entity, CreatureResources)> {
+        let mut query = self
+            .world
+            .query::<(Entity, Option<&Life>, Option<&Mana>, Option<&ActionPoints>)>();
+
+        query
+            .iter(&self.world)
+            .map(|(entity, life, mana, action_points)| {
+                (entity, CreatureResources::new(life, mana, action_points))
+            })
+            .collect()
+    }
+}
+
+/// Simulates the `action` on a copy of the `world`, reporting what would happen
+///
+/// This is a shortcut for [`Simulation::snapshot`] followed by [`Simulation::run`].
+pub fn simulate(world: &mut World, action: &Action) -> SimulationReport {
+    Simulation::snapshot(world).run(action)
+}
+
+/// How the resources of a single creature were changed by a simulated action
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreatureChange {
+    /// The creature that was changed
+    pub entity: Entity,
+    /// The creature's resources before the action
+    pub before: CreatureResources,
+    /// The creature's resources after the action
+    pub after: CreatureResources,
+}
+
+/// What happened when an action was simulated
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SimulationReport {
+    /// Every RNG value that was used by the action, in order
+    pub draws: Vec<RngDraw>,
+    /// Every creature whose resources were changed by the action
+    pub changes: Vec<CreatureChange>,
+    /// The messages that the action would have printed to the terminal
+    pub messages: Vec<String>,
+}
+
+impl SimulationReport {
+    /// The change in resources of the `entity`, if the action changed them
+    #[must_use]
+    pub fn change_for(&self, entity: Entity) -> Option<&CreatureChange> {
+        self.changes.iter().find(|change| change.entity == entity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actions::{ActionData, Actions, Cost, Step, Targeting};
+    use crate::combat_statistics::Resource;
+    use crate::rng::RngWindow;
+    use crate::status_effects::{EffectCategory, EffectKind, RemovalCause, StatusEffect};
+
+    const LIFE: u8 = 20;
+
+    /// A world with a creature that can attack and a target that cannot dodge
+    fn combat() -> (World, Entity, Entity) {
+        let mut world = World::new();
+        let mut rng = Rng::default();
+        let rng_outputs = RNGOutputs::new(&mut rng, RngWindow::default());
+        world.insert_resource(rng);
+        world.insert_resource(rng_outputs);
+        world.insert_resource(RngLog::default());
+
+        let actor = world
+            .spawn()
+            .insert_bundle((Life::new(LIFE), ActionPoints::new(10), Damage::new(2, 4)))
+            .id();
+        let target = world.spawn().insert(Life::new(LIFE)).id();
+        (world, actor, target)
+    }
+
+    /// The `action`, queued for the `actor` to use on the `target`
+    fn queued(action: Action, actor: Entity, target: Entity) -> Action {
+        let id = action.id();
+        let mut actions = Actions::default();
+        actions.insert(action);
+        let resources = CreatureResources {
+            life: Some(LIFE),
+            mana: Some(10),
+            action_points: Some(10),
+        };
+        actions
+            .queue(id.clone(), actor, vec![target], &resources)
+            .unwrap();
+        actions.get(&id).unwrap().clone()
+    }
+
+    #[test]
+    fn the_source_world_is_unchanged() {
+        let (mut world, actor, target) = combat();
+        let attack = queued(Action::attack(), actor, target);
+        let index = world.get_resource::<RNGOutputs>().unwrap().index();
+
+        let report = simulate(&mut world, &attack);
+        assert!(!report.draws.is_empty());
+
+        assert_eq!(world.get::<Life>(target).unwrap().current(), LIFE);
+        assert_eq!(world.get_resource::<RNGOutputs>().unwrap().index(), index);
+        assert_eq!(world.get_resource::<RngLog>().unwrap().since(0).count(), 0);
+    }
+
+    #[test]
+    fn attacks_that_hit_use_every_draw() {
+        let (mut world, actor, target) = combat();
+        let attack = queued(Action::attack(), actor, target);
+
+        let report = simulate(&mut world, &attack);
+        assert_eq!(Some(report.draws.len()), attack.max_draws());
+    }
+
+    #[test]
+    fn changes_report_the_targets_life_loss() {
+        let (mut world, actor, target) = combat();
+        let attack = queued(Action::attack(), actor, target);
+
+        let report = simulate(&mut world, &attack);
+        let change = report.change_for(target).unwrap();
+        assert_eq!(change.before.life, Some(LIFE));
+        assert!(change.after.life.unwrap() < LIFE);
+
+        // Attacking costs action points, but not life
+        let change = report.change_for(actor).unwrap();
+        assert_eq!(change.before.life, change.after.life);
+    }
+
+    #[test]
+    fn actors_without_status_effects_steal_nothing() {
+        let (mut world, actor, target) = combat();
+        let mut effects = StatusEffects::default();
+        effects.apply(StatusEffect::new(EffectKind::Evasion(10), 3));
+        world.entity_mut(target).insert(effects);
+
+        let siphon = ActionData {
+            name: "Siphon".to_string(),
+            cost: Cost::default(),
+            targeting: Targeting::SingleEnemy,
+            steps: vec![Step::Steal {
+                category: EffectCategory::Buff,
+            }],
+        }
+        .to_action();
+        let siphon = queued(siphon, actor, target);
+
+        let mut simulation = Simulation::snapshot(&mut world);
+        simulation.run(&siphon);
+        let effects = simulation.world().get::<StatusEffects>(target).unwrap();
+        assert!(effects.has_category(EffectCategory::Buff));
+
+        // Creatures that can hold status effects take them away
+        world.entity_mut(actor).insert(StatusEffects::default());
+        let mut simulation = Simulation::snapshot(&mut world);
+        simulation.run(&siphon);
+        let world = simulation.world();
+        assert!(!world
+            .get::<StatusEffects>(target)
+            .unwrap()
+            .has_category(EffectCategory::Buff));
+        assert!(world
+            .get::<StatusEffects>(actor)
+            .unwrap()
+            .has_category(EffectCategory::Buff));
+    }
+
+    #[test]
+    fn removed_effects_are_reported_with_their_cause() {
+        let (mut world, actor, target) = combat();
+        world.entity_mut(actor).insert(StatusEffects::default());
+        let mut effects = StatusEffects::default();
+        effects.apply(StatusEffect::new(EffectKind::Poison(3), 2));
+        effects.apply(StatusEffect::new(EffectKind::Evasion(10), 3));
+        world.entity_mut(target).insert(effects);
+
+        let meddle = ActionData {
+            name: "Meddle".to_string(),
+            cost: Cost::default(),
+            targeting: Targeting::SingleEnemy,
+            steps: vec![
+                Step::Steal {
+                    category: EffectCategory::Buff,
+                },
+                Step::Invert {
+                    category: EffectCategory::Debuff,
+                },
+                Step::Remove {
+                    category: EffectCategory::Magic,
+                },
+            ],
+        }
+        .to_action();
+        let meddle = queued(meddle, actor, target);
+
+        let mut simulation = Simulation::snapshot(&mut world);
+        simulation.run(&meddle);
+        let events = simulation
+            .world()
+            .get_resource::<Events<EffectRemoved>>()
+            .unwrap();
+        let removed: Vec<EffectRemoved> = events.get_reader().iter(events).copied().collect();
+
+        assert_eq!(
+            removed,
+            [
+                EffectRemoved {
+                    creature: target,
+                    effect: StatusEffect::new(EffectKind::Evasion(10), 3),
+                    cause: RemovalCause::Stolen { by: actor },
+                },
+                EffectRemoved {
+                    creature: target,
+                    effect: StatusEffect::new(EffectKind::Poison(3), 2),
+                    cause: RemovalCause::Inverted {
+                        into: EffectKind::Regeneration(3)
+                    },
+                },
+                EffectRemoved {
+                    creature: target,
+                    effect: StatusEffect::new(EffectKind::Regeneration(3), 2),
+                    cause: RemovalCause::Removed,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,135 @@
+//! Spells: actions that can fizzle, which creatures cast from their [`Spellbook`]
+//!
+//! An action is a spell if any of its steps is a [`Step::Cast`](crate::actions::Step::Cast),
+//! which rolls against a fail threshold lowered by the caster's [`Intelligence`](crate::combat_statistics::Intelligence).
+
+use crate::actions::{ActionId, ActionQueue, Actions};
+use crate::combat_statistics::{ActionPoints, CreatureResources, Life, Mana};
+use crate::creatures::Player;
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+use leafwing_terminal::*;
+
+/// Adds the `cast` and `spells` terminal commands
+///
+/// Terminal commands are only added if the [`TerminalPlugin`] was added first.
+/// Spells can be cast with `cast <spell> [target]`, e.g. `cast fire bolt goblin1`,
+/// and the player's [`Spellbook`] can be listed with `spells`.
+pub struct SpellPlugin;
+
+impl Plugin for SpellPlugin {
+    fn build(&self, app: &mut App) {
+        if app.world.contains_resource::<TerminalConfiguration>() {
+            app.add_terminal_command::<CastCommand, _, _>(cast_command)
+                .add_terminal_command::<SpellsCommand, _, _>(spells_command);
+        }
+    }
+}
+
+/// The spells that a creature knows how to cast
+///
+/// Spells are kept separate from a creature's [`AvailableActions`](crate::actions::AvailableActions),
+/// and are added to the spellbook when learned with a [`LearnAction`](crate::actions::LearnAction).
+#[derive(Component, Debug, Default, Clone)]
+pub struct Spellbook {
+    set: HashSet<ActionId>,
+}
+
+impl Spellbook {
+    /// Adds the spell with the `spell_id`
+    pub fn insert(&mut self, spell_id: ActionId) {
+        self.set.insert(spell_id);
+    }
+
+    /// Removes the spell with the `spell_id`, returning whether it was present
+    pub fn remove(&mut self, spell_id: &ActionId) -> bool {
+        self.set.remove(spell_id)
+    }
+
+    /// Is the spell with the `spell_id` in this spellbook?
+    #[must_use]
+    pub fn contains(&self, spell_id: &ActionId) -> bool {
+        self.set.contains(spell_id)
+    }
+
+    /// Lists the spells in alphabetical order of their names
+    #[must_use]
+    pub fn list(&self) -> Vec<ActionId> {
+        let mut vec: Vec<ActionId> = self.set.iter().cloned().collect();
+        vec.sort_by_key(ToString::to_string);
+        vec
+    }
+
+    /// Are there no spells in this spellbook?
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.set.is_empty()
+    }
+}
+
+/// Casts the spell with the provided name, optionally on the creature with the `target` name
+#[derive(TerminalCommand)]
+#[terminal_command(name = "cast")]
+struct CastCommand {
+    /// The name of the spell, ignoring case, spaces and underscores
+    spell: String,
+    /// The name of the creature to cast the spell on, ignoring case, spaces and underscores
+    target: Option<String>,
+}
+
+/// Lists the spells in the player's spellbook, along with their costs and how many RNG values they use
+#[derive(TerminalCommand)]
+#[terminal_command(name = "spells")]
+struct SpellsCommand;
+
+fn cast_command(mut terminal_command: TerminalCommand<CastCommand>, mut action_queue: ActionQueue) {
+    if let Some(CastCommand { spell, target }) = terminal_command.take() {
+        let result = action_queue.resolve(&spell).and_then(|spell_id| {
+            let target = target
+                .map(|target| action_queue.resolve_target(&target))
+                .transpose()?;
+
+            action_queue.cast(spell_id, target)
+        });
+
+        if let Err(error) = result {
+            terminal_command.reply(error.to_string());
+        }
+    }
+}
+
+fn spells_command(
+    mut terminal_command: TerminalCommand<SpellsCommand>,
+    actions: Res<Actions>,
+    player_query: Query<
+        (
+            &Spellbook,
+            Option<&Life>,
+            Option<&Mana>,
+            Option<&ActionPoints>,
+        ),
+        With<Player>,
+    >,
+) {
+    if terminal_command.take().is_none() {
+        return;
+    }
+
+    let (spellbook, life, mana, action_points) = match player_query.get_single() {
+        Ok(player) => player,
+        Err(_) => {
+            terminal_command.reply_failed("There is no player to list the spells of.");
+            return;
+        }
+    };
+    let resources = CreatureResources::new(life, mana, action_points);
+
+    let lines = actions.describe(&resources, |spell_id| spellbook.contains(spell_id));
+    if lines.is_empty() {
+        terminal_command.reply("Your spellbook is empty.");
+    }
+
+    for line in lines {
+        terminal_command.reply(line);
+    }
+}
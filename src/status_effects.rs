@@ -0,0 +1,643 @@
+//! Buffs and debuffs that last for several turns, triggering at the start of each of the affected creature's turns
+
+use crate::combat_flow::TurnStarted;
+use crate::combat_statistics::{Damage, Life, Resource};
+use crate::rng::{get_next_rng_value, DrawLabel, RNGOutputs, Rng, RngLog, RollKind};
+use bevy::prelude::*;
+use leafwing_terminal::PrintTerminalLine;
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+/// Lets status effects be removed, stolen and inverted, reporting each removal with an [`EffectRemoved`] event
+///
+/// Effects trigger at the start of each turn, which is handled by the [`CombatFlowPlugin`](crate::combat_flow::CombatFlowPlugin).
+pub struct StatusEffectPlugin;
+
+impl Plugin for StatusEffectPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<EffectRemoved>();
+    }
+}
+
+/// A kind of status effect, along with how strong it is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum EffectKind {
+    /// Restores this much life at the start of each turn
+    Regeneration(u8),
+    /// Loses this much life at the start of each turn
+    Bleeding(u8),
+    /// Rolls damage from 1 up to this much at the start of each turn
+    Poison(u8),
+    /// Rolls whenever the creature starts an action aimed at its enemies, turning the action on itself if the roll is below this chance
+    Confusion(u8),
+    /// Adds this much to the creature's [`DodgeChance`](crate::combat_statistics::DodgeChance)
+    Evasion(u8),
+    /// Adds this much to the creature's [`CritChance`](crate::combat_statistics::CritChance)
+    LuckyStrike(u8),
+}
+
+impl EffectKind {
+    /// What happens when a creature gains this effect while already affected by it
+    #[must_use]
+    pub fn stacking(&self) -> Stacking {
+        match self {
+            EffectKind::Regeneration(_) => Stacking::Extend,
+            EffectKind::Bleeding(_) => Stacking::Independent,
+            EffectKind::Poison(_) => Stacking::Extend,
+            EffectKind::Confusion(_) => Stacking::Refresh,
+            EffectKind::Evasion(_) | EffectKind::LuckyStrike(_) => Stacking::Refresh,
+        }
+    }
+
+    /// Does this effect draw an RNG value each time it triggers?
+    ///
+    /// Confusion triggers when the creature acts, rather than at the start of its turn.
+    #[must_use]
+    pub fn uses_rng(&self) -> bool {
+        matches!(self, EffectKind::Poison(_) | EffectKind::Confusion(_))
+    }
+
+    /// The groups that this effect belongs to
+    #[must_use]
+    pub fn categories(&self) -> &'static [EffectCategory] {
+        use EffectCategory::*;
+
+        match self {
+            EffectKind::Regeneration(_) => &[Buff, Magic],
+            EffectKind::Bleeding(_) => &[Debuff],
+            EffectKind::Poison(_) => &[Debuff, Poison],
+            EffectKind::Confusion(_) => &[Debuff, Magic, Curse],
+            EffectKind::Evasion(_) | EffectKind::LuckyStrike(_) => &[Buff, Magic],
+        }
+    }
+
+    /// Does this effect belong to the `category`?
+    #[must_use]
+    pub fn is_in(&self, category: EffectCategory) -> bool {
+        self.categories().contains(&category)
+    }
+
+    /// The effect with the opposite result and the same strength, if there is one
+    #[must_use]
+    pub fn inverted(&self) -> Option<EffectKind> {
+        match self {
+            EffectKind::Regeneration(amount) => Some(EffectKind::Bleeding(*amount)),
+            EffectKind::Bleeding(amount) | EffectKind::Poison(amount) => {
+                Some(EffectKind::Regeneration(*amount))
+            }
+            EffectKind::Confusion(_) | EffectKind::Evasion(_) | EffectKind::LuckyStrike(_) => None,
+        }
+    }
+}
+
+impl Display for EffectKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            EffectKind::Regeneration(_) => "regeneration",
+            EffectKind::Bleeding(_) => "bleeding",
+            EffectKind::Poison(_) => "poison",
+            EffectKind::Confusion(_) => "confusion",
+            EffectKind::Evasion(_) => "evasion",
+            EffectKind::LuckyStrike(_) => "lucky strike",
+        })
+    }
+}
+
+/// A group of status effects, which can be operated on as a whole
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[allow(missing_docs)]
+pub enum EffectCategory {
+    Buff,
+    Debuff,
+    Poison,
+    Magic,
+    Curse,
+}
+
+impl Display for EffectCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            EffectCategory::Buff => "buff",
+            EffectCategory::Debuff => "debuff",
+            EffectCategory::Poison => "poison",
+            EffectCategory::Magic => "magic",
+            EffectCategory::Curse => "curse",
+        })
+    }
+}
+
+/// Sent whenever a status effect is taken away from a creature
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EffectRemoved {
+    /// The creature that lost the effect
+    pub creature: Entity,
+    /// The effect that was lost, with the duration it had left
+    pub effect: StatusEffect,
+    /// Why the effect was lost
+    pub cause: RemovalCause,
+}
+
+/// Why a status effect was taken away from a creature
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemovalCause {
+    /// The effect ran out of turns
+    Expired,
+    /// The effect was removed by an action
+    Removed,
+    /// The effect was taken by another creature, which now has it
+    Stolen {
+        /// The creature that took the effect
+        by: Entity,
+    },
+    /// The effect was replaced by its inverse
+    Inverted {
+        /// The effect that replaced it
+        into: EffectKind,
+    },
+}
+
+/// How a status effect combines with an identical effect that the creature already has
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stacking {
+    /// The new duration is added to the remaining duration
+    Extend,
+    /// The remaining duration becomes the longer of the two
+    Refresh,
+    /// Each application is a separate effect, with its own duration
+    Independent,
+}
+
+/// A single status effect, which lasts for a number of turns
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatusEffect {
+    /// What the effect does
+    pub kind: EffectKind,
+    /// The number of turns that the effect will trigger for
+    pub duration: u8,
+}
+
+impl StatusEffect {
+    /// Creates a new [`StatusEffect`], lasting for `duration` turns
+    #[must_use]
+    pub fn new(kind: EffectKind, duration: u8) -> Self {
+        StatusEffect { kind, duration }
+    }
+}
+
+/// The status effects that a creature is affected by
+#[derive(Component, Debug, Default, Clone, PartialEq, Eq)]
+pub struct StatusEffects {
+    effects: Vec<StatusEffect>,
+}
+
+impl StatusEffects {
+    /// Adds the `effect`, combining it with any identical effect according to its [`Stacking`]
+    ///
+    /// Effects with no duration are ignored.
+    pub fn apply(&mut self, effect: StatusEffect) {
+        if effect.duration == 0 {
+            return;
+        }
+
+        let existing = self
+            .effects
+            .iter_mut()
+            .find(|existing| existing.kind == effect.kind);
+
+        match (effect.kind.stacking(), existing) {
+            (Stacking::Extend, Some(existing)) => {
+                existing.duration = existing.duration.saturating_add(effect.duration);
+            }
+            (Stacking::Refresh, Some(existing)) => {
+                existing.duration = existing.duration.max(effect.duration);
+            }
+            _ => self.effects.push(effect),
+        }
+    }
+
+    /// The effects, in the order that they were gained
+    pub fn iter(&self) -> impl Iterator<Item = &StatusEffect> {
+        self.effects.iter()
+    }
+
+    /// Is the creature affected by any effect of the same kind as `kind`, regardless of its strength?
+    #[must_use]
+    pub fn contains(&self, kind: EffectKind) -> bool {
+        self.effects
+            .iter()
+            .any(|effect| std::mem::discriminant(&effect.kind) == std::mem::discriminant(&kind))
+    }
+
+    /// The effects that belong to the `category`, in the order that they were gained
+    pub fn in_category(&self, category: EffectCategory) -> impl Iterator<Item = &StatusEffect> {
+        self.effects
+            .iter()
+            .filter(move |effect| effect.kind.is_in(category))
+    }
+
+    /// Is the creature affected by any effect in the `category`?
+    #[must_use]
+    pub fn has_category(&self, category: EffectCategory) -> bool {
+        self.in_category(category).next().is_some()
+    }
+
+    /// Removes and returns every effect in the `category`
+    pub fn remove_category(&mut self, category: EffectCategory) -> Vec<StatusEffect> {
+        let (removed, remaining) = self
+            .effects
+            .drain(..)
+            .partition(|effect| effect.kind.is_in(category));
+        self.effects = remaining;
+        removed
+    }
+
+    /// Adds `turns` to the duration of every effect in the `category`, returning the extended effects
+    pub fn extend_category(&mut self, category: EffectCategory, turns: u8) -> Vec<StatusEffect> {
+        self.effects
+            .iter_mut()
+            .filter(|effect| effect.kind.is_in(category))
+            .map(|effect| {
+                effect.duration = effect.duration.saturating_add(turns);
+                *effect
+            })
+            .collect()
+    }
+
+    /// Replaces every effect in the `category` with its [inverse](EffectKind::inverted), keeping its duration
+    ///
+    /// Effects without an inverse are left alone.
+    /// Returns each replaced effect, along with the effect that replaced it.
+    pub fn invert_category(
+        &mut self,
+        category: EffectCategory,
+    ) -> Vec<(StatusEffect, StatusEffect)> {
+        let (invertible, remaining) = self
+            .effects
+            .drain(..)
+            .partition(|effect| effect.kind.is_in(category) && effect.kind.inverted().is_some());
+        self.effects = remaining;
+
+        invertible
+            .into_iter()
+            .filter_map(|effect: StatusEffect| {
+                let inverse = StatusEffect::new(effect.kind.inverted()?, effect.duration);
+                self.apply(inverse);
+                Some((effect, inverse))
+            })
+            .collect()
+    }
+
+    /// The chance that the creature turns an action on itself, from its strongest [`EffectKind::Confusion`]
+    #[must_use]
+    pub fn confusion(&self) -> Option<u8> {
+        self.effects
+            .iter()
+            .filter_map(|effect| match effect.kind {
+                EffectKind::Confusion(chance) => Some(chance),
+                _ => None,
+            })
+            .max()
+    }
+
+    /// The total bonus to dodge chance granted by [`EffectKind::Evasion`]
+    #[must_use]
+    pub fn dodge_bonus(&self) -> u8 {
+        self.effects
+            .iter()
+            .fold(0, |total, effect| match effect.kind {
+                EffectKind::Evasion(bonus) => total.saturating_add(bonus),
+                _ => total,
+            })
+    }
+
+    /// The total bonus to crit chance granted by [`EffectKind::LuckyStrike`]
+    #[must_use]
+    pub fn crit_bonus(&self) -> u8 {
+        self.effects
+            .iter()
+            .fold(0, |total, effect| match effect.kind {
+                EffectKind::LuckyStrike(bonus) => total.saturating_add(bonus),
+                _ => total,
+            })
+    }
+
+    /// Is the creature free of status effects?
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.effects.is_empty()
+    }
+
+    /// Counts down the duration of every effect by one turn, removing and returning those that have run out
+    pub fn tick(&mut self) -> Vec<StatusEffect> {
+        for effect in &mut self.effects {
+            effect.duration = effect.duration.saturating_sub(1);
+        }
+
+        let (expired, remaining) = self
+            .effects
+            .drain(..)
+            .partition(|effect| effect.duration == 0);
+        self.effects = remaining;
+        expired
+    }
+}
+
+/// Triggers the status effects of each creature whose turn has just started, then counts down their durations
+///
+/// Runs after action points are refilled.
+pub(crate) fn tick_status_effects(
+    mut turn_started: EventReader<TurnStarted>,
+    mut query: Query<(&mut StatusEffects, Option<&Name>, Option<&mut Life>)>,
+    mut rng: ResMut<Rng>,
+    mut rng_outputs: ResMut<RNGOutputs>,
+    mut rng_log: ResMut<RngLog>,
+    mut removed: EventWriter<EffectRemoved>,
+    mut lines: EventWriter<PrintTerminalLine>,
+) {
+    for TurnStarted { creature, .. } in turn_started.iter() {
+        let (mut status_effects, name, mut life) = match query.get_mut(*creature) {
+            Ok(components) => components,
+            Err(_) => continue,
+        };
+        let name = name.map_or("Someone", |name| name.as_str());
+
+        for effect in status_effects.effects.clone() {
+            let line = match (effect.kind, life.as_deref_mut()) {
+                (EffectKind::Regeneration(amount), Some(life)) => {
+                    *life += amount;
+                    format!(
+                        "{name} regenerates {amount} life, and now has {} life.",
+                        life.current()
+                    )
+                }
+                (EffectKind::Bleeding(amount), Some(life)) => {
+                    *life -= amount;
+                    format!(
+                        "{name} bleeds for {amount} damage, leaving them with {} life.",
+                        life.current()
+                    )
+                }
+                (EffectKind::Poison(max), Some(life)) => {
+                    let value = get_next_rng_value(
+                        &mut *rng,
+                        &mut *rng_outputs,
+                        &mut *rng_log,
+                        DrawLabel::new(*creature, "Poison", RollKind::Status),
+                    );
+                    let dealt = Damage::new(1, max.max(1)).compute(value);
+                    *life -= dealt;
+
+                    format!(
+                        "{name} rolls {value} against their poison: they take {dealt} damage, leaving them with {} life.",
+                        life.current()
+                    )
+                }
+                // Confusion is rolled by `roll_confusion` when the creature acts,
+                // and bonuses to other stats are applied whenever those stats are used
+                (
+                    EffectKind::Confusion(_) | EffectKind::Evasion(_) | EffectKind::LuckyStrike(_),
+                    _,
+                ) => continue,
+                // Creatures without life cannot gain or lose it
+                (_, None) => continue,
+            };
+            lines.send(PrintTerminalLine::new(line));
+        }
+
+        for expired in status_effects.tick() {
+            lines.send(PrintTerminalLine::new(format!(
+                "{name}'s {} wears off.",
+                expired.kind
+            )));
+            removed.send(EffectRemoved {
+                creature: *creature,
+                effect: expired,
+                cause: RemovalCause::Expired,
+            });
+        }
+    }
+}
+
+/// Rolls against the confusion of the `actor` as they start an action aimed at their enemies,
+/// returning whether they turn it on themselves instead
+///
+/// Creatures that are not confused draw no RNG value.
+pub(crate) fn roll_confusion(world: &mut World, actor: Entity) -> bool {
+    let chance = match world
+        .get::<StatusEffects>(actor)
+        .and_then(StatusEffects::confusion)
+    {
+        Some(chance) => chance,
+        None => return false,
+    };
+
+    let value = world.resource_scope(|world, mut rng_outputs: Mut<RNGOutputs>| {
+        world.resource_scope(|world, mut rng_log: Mut<RngLog>| {
+            let mut rng = world.get_resource_mut::<Rng>().unwrap();
+            get_next_rng_value(
+                &mut *rng,
+                &mut *rng_outputs,
+                &mut *rng_log,
+                DrawLabel::new(actor, "Confusion", RollKind::Status),
+            )
+        })
+    });
+    let confused = value < chance;
+
+    let name = world
+        .get::<Name>(actor)
+        .map_or("Someone", |name| name.as_str());
+    let line = if confused {
+        format!("{name} rolls {value} against their confusion: they turn on themselves!")
+    } else {
+        format!("{name} rolls {value} against their confusion: they keep their focus.")
+    };
+    if let Some(mut lines) = world.get_resource_mut::<Events<PrintTerminalLine>>() {
+        lines.send(PrintTerminalLine::new(line));
+    }
+
+    confused
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::RngWindow;
+
+    fn effects(applied: impl IntoIterator<Item = (EffectKind, u8)>) -> Vec<StatusEffect> {
+        let mut effects = StatusEffects::default();
+        for (kind, duration) in applied {
+            effects.apply(StatusEffect::new(kind, duration));
+        }
+        effects.iter().copied().collect()
+    }
+
+    #[test]
+    fn extending_effects_add_their_durations() {
+        assert_eq!(
+            effects([(EffectKind::Poison(3), 2), (EffectKind::Poison(3), 4)]),
+            [StatusEffect::new(EffectKind::Poison(3), 6)]
+        );
+        assert_eq!(
+            effects([
+                (EffectKind::Regeneration(1), 200),
+                (EffectKind::Regeneration(1), 100)
+            ]),
+            [StatusEffect::new(EffectKind::Regeneration(1), u8::MAX)]
+        );
+    }
+
+    #[test]
+    fn refreshing_effects_keep_the_longer_duration() {
+        assert_eq!(
+            effects([(EffectKind::Evasion(51), 3), (EffectKind::Evasion(51), 1)]),
+            [StatusEffect::new(EffectKind::Evasion(51), 3)]
+        );
+        assert_eq!(
+            effects([(EffectKind::Evasion(51), 1), (EffectKind::Evasion(51), 3)]),
+            [StatusEffect::new(EffectKind::Evasion(51), 3)]
+        );
+    }
+
+    #[test]
+    fn independent_effects_keep_separate_durations() {
+        assert_eq!(
+            effects([(EffectKind::Bleeding(4), 5), (EffectKind::Bleeding(4), 2)]),
+            [
+                StatusEffect::new(EffectKind::Bleeding(4), 5),
+                StatusEffect::new(EffectKind::Bleeding(4), 2)
+            ]
+        );
+    }
+
+    #[test]
+    fn effects_of_different_strengths_do_not_stack() {
+        assert_eq!(
+            effects([(EffectKind::Poison(3), 2), (EffectKind::Poison(5), 4)]),
+            [
+                StatusEffect::new(EffectKind::Poison(3), 2),
+                StatusEffect::new(EffectKind::Poison(5), 4)
+            ]
+        );
+    }
+
+    #[test]
+    fn effects_without_a_duration_are_ignored() {
+        assert!(effects([(EffectKind::Poison(3), 0)]).is_empty());
+    }
+
+    #[test]
+    fn extending_a_category_lengthens_only_its_effects() {
+        let mut effects = StatusEffects::default();
+        effects.apply(StatusEffect::new(EffectKind::Poison(3), 2));
+        effects.apply(StatusEffect::new(EffectKind::Evasion(51), 1));
+
+        assert_eq!(
+            effects.extend_category(EffectCategory::Debuff, 3),
+            [StatusEffect::new(EffectKind::Poison(3), 5)]
+        );
+        assert!(effects.iter().eq(&[
+            StatusEffect::new(EffectKind::Poison(3), 5),
+            StatusEffect::new(EffectKind::Evasion(51), 1)
+        ]));
+    }
+
+    #[test]
+    fn ticking_removes_expired_effects() {
+        let mut effects = StatusEffects::default();
+        effects.apply(StatusEffect::new(EffectKind::Poison(3), 1));
+        effects.apply(StatusEffect::new(EffectKind::Evasion(51), 2));
+
+        assert_eq!(
+            effects.tick(),
+            [StatusEffect::new(EffectKind::Poison(3), 0)]
+        );
+        assert!(effects
+            .iter()
+            .eq(&[StatusEffect::new(EffectKind::Evasion(51), 1)]));
+    }
+
+    #[test]
+    fn removing_a_category_takes_only_its_effects() {
+        let mut effects = StatusEffects::default();
+        effects.apply(StatusEffect::new(EffectKind::Poison(3), 2));
+        effects.apply(StatusEffect::new(EffectKind::Evasion(51), 1));
+        effects.apply(StatusEffect::new(EffectKind::Bleeding(2), 4));
+
+        assert_eq!(
+            effects.remove_category(EffectCategory::Debuff),
+            [
+                StatusEffect::new(EffectKind::Poison(3), 2),
+                StatusEffect::new(EffectKind::Bleeding(2), 4)
+            ]
+        );
+        assert!(effects
+            .iter()
+            .eq(&[StatusEffect::new(EffectKind::Evasion(51), 1)]));
+        assert!(effects.remove_category(EffectCategory::Debuff).is_empty());
+    }
+
+    #[test]
+    fn inverted_effects_stack_with_existing_ones() {
+        let mut effects = StatusEffects::default();
+        effects.apply(StatusEffect::new(EffectKind::Regeneration(2), 3));
+        effects.apply(StatusEffect::new(EffectKind::Bleeding(2), 4));
+        effects.apply(StatusEffect::new(EffectKind::Poison(2), 2));
+        effects.apply(StatusEffect::new(EffectKind::Confusion(51), 2));
+
+        assert_eq!(
+            effects.invert_category(EffectCategory::Debuff),
+            [
+                (
+                    StatusEffect::new(EffectKind::Bleeding(2), 4),
+                    StatusEffect::new(EffectKind::Regeneration(2), 4)
+                ),
+                (
+                    StatusEffect::new(EffectKind::Poison(2), 2),
+                    StatusEffect::new(EffectKind::Regeneration(2), 2)
+                )
+            ]
+        );
+
+        // Regeneration extends, and confusion has no inverse
+        assert!(effects.iter().eq(&[
+            StatusEffect::new(EffectKind::Regeneration(2), 9),
+            StatusEffect::new(EffectKind::Confusion(51), 2)
+        ]));
+    }
+
+    #[test]
+    fn expired_effects_are_reported() {
+        let mut world = World::new();
+        let mut rng = Rng::default();
+        let rng_outputs = RNGOutputs::new(&mut rng, RngWindow::default());
+        world.insert_resource(rng);
+        world.insert_resource(rng_outputs);
+        world.insert_resource(RngLog::default());
+        world.insert_resource(Events::<TurnStarted>::default());
+        world.insert_resource(Events::<EffectRemoved>::default());
+        world.insert_resource(Events::<PrintTerminalLine>::default());
+
+        let mut effects = StatusEffects::default();
+        effects.apply(StatusEffect::new(EffectKind::Evasion(51), 1));
+        effects.apply(StatusEffect::new(EffectKind::Bleeding(2), 2));
+        let creature = world.spawn().insert(effects).id();
+
+        world
+            .get_resource_mut::<Events<TurnStarted>>()
+            .unwrap()
+            .send(TurnStarted { creature, round: 0 });
+        SystemStage::single(tick_status_effects).run(&mut world);
+
+        let events = world.get_resource::<Events<EffectRemoved>>().unwrap();
+        let removed: Vec<EffectRemoved> = events.get_reader().iter(events).copied().collect();
+        assert_eq!(
+            removed,
+            [EffectRemoved {
+                creature,
+                effect: StatusEffect::new(EffectKind::Evasion(51), 0),
+                cause: RemovalCause::Expired,
+            }]
+        );
+    }
+}
@@ -1,15 +1,91 @@
-//! Tools for constructing linear sequences of [`Systems`](System)
+//! Tools for constructing sequences of [`Systems`](System), which can branch and exit early
 
-use bevy::ecs::system::{IntoSystem, System};
+use bevy::ecs::system::{In, IntoChainSystem, IntoSystem, System};
 use bevy::ecs::world::World;
+use bevy::utils::HashMap;
+use std::fmt::Display;
 
-/// A linear sequence of unchained [`Systems`](System)
+/// What a [`SystemSeq`] should do after one of its steps runs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Flow {
+    /// Run the next step
+    Continue,
+    /// Skip over this many steps, then run the step after them
+    Skip(usize),
+    /// Run the step marked with this label next, as set by [`SystemSeq::label`]
+    ///
+    /// Steps that jump must be added with [`SystemSeq::then_jumping`], so that their labels are checked when the sequence is built.
+    Jump(String),
+    /// Stop: no more steps will be run
+    Abort,
+}
+
+/// A step of a [`SystemSeq`]
+type Step = Box<dyn System<In = (), Out = Flow>>;
+
+/// Systems that can be used as a step of a [`SystemSeq`]
+///
+/// Systems that return a [`Flow`] control which step runs next, while systems that return nothing always continue.
+pub trait IntoStep<Marker> {
+    /// Converts this system into a boxed step
+    fn into_step(self) -> Step;
+}
+
+impl<Params, S: IntoSystem<(), (), Params>> IntoStep<(Params, ())> for S {
+    fn into_step(self) -> Step {
+        Box::new(self.chain(continue_flow))
+    }
+}
+
+impl<Params, S: IntoSystem<(), Flow, Params>> IntoStep<(Params, Flow)> for S {
+    fn into_step(self) -> Step {
+        Box::new(self.system())
+    }
+}
+
+/// Always continues on to the next step
+fn continue_flow(In(()): In<()>) -> Flow {
+    Flow::Continue
+}
+
+/// The reasons that a [`SystemSeq`] could not be built
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SeqError {
+    /// The same label was used for more than one step
+    DuplicateLabel(String),
+    /// A step can jump to a label that no step is marked with
+    UnknownLabel(String),
+}
+
+impl Display for SeqError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SeqError::DuplicateLabel(label) => {
+                f.write_fmt(format_args!("The label {label} is used more than once."))
+            }
+            SeqError::UnknownLabel(label) => {
+                f.write_fmt(format_args!("No step is labelled {label}."))
+            }
+        }
+    }
+}
+
+impl std::error::Error for SeqError {}
+
+/// A sequence of unchained [`Systems`](System), run one step at a time
 ///
 /// Each system runs exclusively on the world.
+/// By default, steps run in order; steps that return a [`Flow`] can skip ahead, jump to a label or stop the sequence.
+/// Call [`SystemSeq::build`] once every step is added, to check that each jump has somewhere to go.
 #[derive(Default)]
 pub struct SystemSeq {
-    systems: Vec<Box<dyn System<In = (), Out = ()>>>,
+    systems: Vec<Step>,
     initialized: Vec<bool>,
+    labels: HashMap<String, usize>,
+    /// The labels that each step can jump to, by the index of the step
+    jumps: Vec<(usize, String)>,
+    /// Problems found while adding steps, reported by [`SystemSeq::build`]
+    errors: Vec<SeqError>,
     index: usize,
 }
 
@@ -20,21 +96,124 @@ impl SystemSeq {
         SystemSeq {
             systems: Vec::new(),
             initialized: Vec::new(),
+            labels: HashMap::default(),
+            jumps: Vec::new(),
+            errors: Vec::new(),
             index: 0,
         }
     }
 
     /// Appends a system to the end of the current list of systems
     #[must_use]
-    pub fn then<Params, S: IntoSystem<(), (), Params>>(mut self, system: S) -> Self {
-        let i = self.len() + 1;
-        let boxed_system: Box<dyn System<In = (), Out = ()>> = Box::new(system.system());
+    pub fn then<Marker, S: IntoStep<Marker>>(mut self, system: S) -> Self {
+        self.systems.push(system.into_step());
+        self.initialized.push(false);
+        self
+    }
+
+    /// Appends a system that may return a [`Flow::Jump`] to any of the `targets`
+    ///
+    /// [`SystemSeq::build`] fails if any of the targets is not a label in the sequence.
+    #[must_use]
+    pub fn then_jumping<Marker, S: IntoStep<Marker>>(
+        mut self,
+        system: S,
+        targets: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        let index = self.len();
+        self.jumps
+            .extend(targets.into_iter().map(|target| (index, target.into())));
+        self.then(system)
+    }
+
+    /// Marks the next system that is added with the `label`, so that it can be the target of a [`Flow::Jump`]
+    ///
+    /// Labels that are already in use are reported by [`SystemSeq::build`].
+    #[must_use]
+    pub fn label(mut self, label: impl Into<String>) -> Self {
+        let label = label.into();
+
+        if self.labels.contains_key(&label) {
+            self.errors.push(SeqError::DuplicateLabel(label));
+        } else {
+            self.labels.insert(label, self.len());
+        }
+        self
+    }
+
+    /// Appends all of the systems in `seq` to the end of the current list of systems
+    ///
+    /// Labels used by both sequences are reported by [`SystemSeq::build`].
+    #[must_use]
+    pub fn append(mut self, seq: SystemSeq) -> Self {
+        let offset = self.len();
+
+        for (label, index) in seq.labels {
+            if self.labels.contains_key(&label) {
+                self.errors.push(SeqError::DuplicateLabel(label));
+            } else {
+                self.labels.insert(label, index + offset);
+            }
+        }
+        self.jumps.extend(
+            seq.jumps
+                .into_iter()
+                .map(|(index, target)| (index + offset, target)),
+        );
+        self.errors.extend(seq.errors);
 
-        self.systems[i] = boxed_system;
-        self.initialized[i] = false;
+        self.systems.extend(seq.systems);
+        self.initialized.extend(seq.initialized);
         self
     }
 
+    /// Checks that the labels are unique, and that every jump declared with [`SystemSeq::then_jumping`] has a label to go to
+    ///
+    /// Sequences that are built successfully never jump to a missing label.
+    pub fn build(self) -> Result<Self, SeqError> {
+        if let Some(error) = self.errors.first() {
+            return Err(error.clone());
+        }
+
+        match self
+            .jumps
+            .iter()
+            .find(|(_, target)| !self.labels.contains_key(target))
+        {
+            Some((_, target)) => Err(SeqError::UnknownLabel(target.clone())),
+            None => Ok(self),
+        }
+    }
+
+    /// Appends the systems in `seq`, which are only run if the `condition` system returns `true`
+    ///
+    /// The condition is run as a step of its own.
+    #[must_use]
+    pub fn when<Params>(
+        self,
+        condition: impl IntoSystem<(), bool, Params>,
+        seq: SystemSeq,
+    ) -> Self {
+        let skipped = seq.len();
+
+        self.then(condition.chain(move |In(passed): In<bool>| {
+            if passed {
+                Flow::Continue
+            } else {
+                Flow::Skip(skipped)
+            }
+        }))
+        .append(seq)
+    }
+
+    /// Appends `n` copies of the sequence created by `build`
+    ///
+    /// `build` is called once per copy, so that each copy has its own systems.
+    #[must_use]
+    pub fn repeat(self, n: usize, build: impl Fn() -> SystemSeq) -> Self {
+        (0..n).fold(self, |seq, _| seq.append(build()))
+    }
+
     /// Initializes the system at the provided `index` on the [`World`]
     fn initialize_one(&mut self, index: usize, world: &mut World) {
         assert!(index <= self.systems.len());
@@ -46,23 +225,32 @@ impl SystemSeq {
         }
     }
 
-    /// Runs the system at the provided `index` on the [`World`]
+    /// Runs the system at the provided `index` on the [`World`], returning which step should run next
     ///
+    /// The system is initialized first if needed.
     /// [`Commands`](bevy::ecs::system::Commands) are flushed after the system runs
-    pub fn run_one(&mut self, index: usize, world: &mut World) {
+    pub fn run_one(&mut self, index: usize, world: &mut World) -> Flow {
         assert!(index <= self.systems.len());
 
-        self.systems[index].run((), world);
+        self.initialize_one(index, world);
+        let flow = self.systems[index].run((), world);
         self.systems[index].apply_buffers(world);
+        flow
     }
 
-    /// Runs the next system at the provided `index` on the [`World`]
+    /// Runs the next system on the [`World`], then moves on according to the [`Flow`] it returned
     ///
-    /// [`Commands`](bevy::ecs::system::Commands) are flushed after the system runs
+    /// [`Commands`](bevy::ecs::system::Commands) are flushed after the system runs.
+    /// Jumps to a label that does not exist stop the sequence, which [`SystemSeq::build`] rules out for declared jumps.
     pub fn run_next(&mut self, world: &mut World) {
         if self.index < self.len() {
-            self.run_one(self.index, world);
-            self.index += 1;
+            self.index = match self.run_one(self.index, world) {
+                Flow::Continue => self.index + 1,
+                Flow::Skip(n) => self.index + 1 + n,
+                Flow::Jump(label) => self.labels.get(&label).copied().unwrap_or(self.len()),
+                Flow::Abort => self.len(),
+            }
+            .min(self.len());
         }
     }
 
@@ -109,3 +297,128 @@ impl SystemSeq {
         self.index = 0;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::ResMut;
+
+    #[derive(Default)]
+    struct Counter(usize);
+
+    fn count(mut counter: ResMut<Counter>) {
+        counter.0 += 1;
+    }
+
+    fn jump_to_end() -> Flow {
+        Flow::Jump("end".to_string())
+    }
+
+    fn abort() -> Flow {
+        Flow::Abort
+    }
+
+    fn never() -> bool {
+        false
+    }
+
+    fn always() -> bool {
+        true
+    }
+
+    fn run(mut seq: SystemSeq) -> usize {
+        let mut world = World::new();
+        world.init_resource::<Counter>();
+
+        seq.run_all(&mut world);
+        world.get_resource::<Counter>().unwrap().0
+    }
+
+    #[test]
+    fn jumps_go_to_their_label() {
+        let seq = SystemSeq::new()
+            .then(count)
+            .then_jumping(jump_to_end, ["end"])
+            .then(count)
+            .label("end")
+            .then(count)
+            .build()
+            .unwrap();
+
+        assert_eq!(run(seq), 2);
+    }
+
+    #[test]
+    fn building_rejects_jumps_to_unknown_labels() {
+        let result = SystemSeq::new()
+            .then_jumping(jump_to_end, ["end"])
+            .label("start")
+            .then(count)
+            .build();
+
+        assert_eq!(
+            result.err(),
+            Some(SeqError::UnknownLabel("end".to_string()))
+        );
+    }
+
+    #[test]
+    fn building_rejects_duplicate_labels() {
+        let labelled = || SystemSeq::new().label("end").then(count);
+        let result = SystemSeq::new()
+            .then_jumping(jump_to_end, ["end"])
+            .append(labelled())
+            .append(labelled())
+            .build();
+
+        assert_eq!(
+            result.err(),
+            Some(SeqError::DuplicateLabel("end".to_string()))
+        );
+    }
+
+    #[test]
+    fn undeclared_jumps_to_unknown_labels_stop_the_sequence() {
+        let seq = SystemSeq::new().then(jump_to_end).then(count);
+
+        assert_eq!(run(seq), 0);
+    }
+
+    #[test]
+    fn failed_conditions_skip_their_systems() {
+        let twice = || SystemSeq::new().then(count).then(count);
+
+        let mut seq = SystemSeq::new().when(never, twice()).then(count);
+        let mut world = World::new();
+        world.init_resource::<Counter>();
+        assert_eq!(seq.run_one(0, &mut world), Flow::Skip(2));
+
+        let seq = SystemSeq::new().when(never, twice()).then(count);
+        assert_eq!(run(seq), 1);
+
+        let seq = SystemSeq::new().when(always, twice()).then(count);
+        assert_eq!(run(seq), 3);
+    }
+
+    #[test]
+    fn repeated_sequences_run_once_per_copy() {
+        let seq = SystemSeq::new().repeat(3, || SystemSeq::new().then(count));
+        assert_eq!(seq.len(), 3);
+        assert_eq!(run(seq), 3);
+
+        let seq = SystemSeq::new().repeat(0, || SystemSeq::new().then(count));
+        assert_eq!(run(seq), 0);
+    }
+
+    #[test]
+    fn aborting_stops_the_sequence() {
+        let mut seq = SystemSeq::new().then(count).then(abort).then(count);
+        let mut world = World::new();
+        world.init_resource::<Counter>();
+
+        seq.run_next(&mut world);
+        seq.run_next(&mut world);
+        assert!(seq.is_finished());
+        assert_eq!(world.get_resource::<Counter>().unwrap().0, 1);
+    }
+}
@@ -5,7 +5,9 @@ use bevy_egui::egui::{self, Color32, Frame, RichText, TextFormat, TextStyle};
 use bevy_egui::EguiContext;
 use leafwing_terminal::{TerminalConfiguration, TerminalPlugin};
 
-use crate::rng::{RNGOutputs, CURRENT_RNG_VALUE_INDEX};
+use crate::creatures::Player;
+use crate::prediction::Prediction;
+use crate::rng::{RNGOutputs, RngLog, Seed};
 
 /// Controls the display of text on the console
 pub struct UiPlugin;
@@ -20,9 +22,15 @@ impl Plugin for UiPlugin {
 
 const CONSOLE_FRACTION: f32 = 2. / 3.;
 
+/// The number of entries from the [`RngLog`] shown in the RNG inspector
+const LOG_LENGTH: usize = 8;
+
 fn sync_full_screen(mut config: ResMut<TerminalConfiguration>, windows: Res<Windows>) {
     if windows.is_changed() {
-        let window = windows.get_primary().unwrap();
+        let window = match windows.get_primary() {
+            Some(window) => window,
+            None => return,
+        };
 
         config.left_pos = 0.0;
         config.top_pos = 0.0;
@@ -33,11 +41,18 @@ fn sync_full_screen(mut config: ResMut<TerminalConfiguration>, windows: Res<Wind
 }
 
 fn spawn_rng_window(
-    mut egui_context: ResMut<EguiContext>, 
+    mut egui_context: ResMut<EguiContext>,
     windows: Res<Windows>,
     rng_values: Res<RNGOutputs>,
+    seed: Res<Seed>,
+    rng_log: Res<RngLog>,
+    prediction: Res<Prediction>,
+    creatures: Query<(Option<&Name>, Option<&Player>)>,
 ) {
-    let window = windows.get_primary().unwrap();
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
     let width = (1f32 - CONSOLE_FRACTION) * window.width();
     let height = window.height();
 
@@ -57,17 +72,20 @@ fn spawn_rng_window(
                 ui_inner.set_min_size((width, height).into());
 
                 ui_inner.label(RichText::new("\nRNG STATE").monospace());
-                ui_inner.label(RichText::new("=========\n").monospace());
-
-                for (i, val) in rng_values.0.iter().enumerate() {
+                ui_inner.label(RichText::new("=========").monospace());
+                ui_inner.label(RichText::new(format!("SEED: {}\n", *seed)).monospace());
 
+                for (i, val) in rng_values.iter().enumerate() {
                     let fraction = (val.result as f32) / 255f32;
 
                     let low_color = Color32::from_rgb(39, 134, 217); // Blue
                     let high_color = Color32::from_rgb(237, 36, 36); // Red
-                    let r = (high_color.r() as f32 - low_color.r() as f32) * fraction + low_color.r() as f32;
-                    let g = (high_color.g() as f32 - low_color.g() as f32) * fraction + low_color.g() as f32;
-                    let b = (high_color.b() as f32 - low_color.b() as f32) * fraction + low_color.b() as f32;
+                    let r = (high_color.r() as f32 - low_color.r() as f32) * fraction
+                        + low_color.r() as f32;
+                    let g = (high_color.g() as f32 - low_color.g() as f32) * fraction
+                        + low_color.g() as f32;
+                    let b = (high_color.b() as f32 - low_color.b() as f32) * fraction
+                        + low_color.b() as f32;
                     let color = Color32::from_rgb(r as u8, g as u8, b as u8);
 
                     let mut job = LayoutJob::default();
@@ -78,7 +96,7 @@ fn spawn_rng_window(
                             style: TextStyle::Monospace,
                             color: color.linear_multiply(0.5),
                             ..Default::default()
-                        }
+                        },
                     );
                     job.append(
                         format!("{:03}", val.result).as_str(),
@@ -87,7 +105,7 @@ fn spawn_rng_window(
                             style: TextStyle::Monospace,
                             color,
                             ..Default::default()
-                        }
+                        },
                     );
                     job.append(
                         format!(" = {:.2}]", fraction).as_str(),
@@ -96,9 +114,9 @@ fn spawn_rng_window(
                             style: TextStyle::Monospace,
                             color: color.linear_multiply(0.8),
                             ..Default::default()
-                        }
+                        },
                     );
-                    if i == CURRENT_RNG_VALUE_INDEX {
+                    if i == rng_values.up_next_position() {
                         job.append(
                             " < UP NEXT",
                             0.0,
@@ -106,13 +124,52 @@ fn spawn_rng_window(
                                 style: TextStyle::Monospace,
                                 color,
                                 ..Default::default()
-                            }
+                            },
+                        );
+                    }
+
+                    // Values before the "up next" value have negative offsets
+                    let index = (rng_values.index() + i).checked_sub(rng_values.up_next_position());
+                    if let Some(annotation) = index.and_then(|index| prediction.get(index)) {
+                        // Values that are used together by a single roll stand out from the rest
+                        let annotation_color = match annotation.part {
+                            Some(_) => Color32::GOLD,
+                            None => color.linear_multiply(0.8),
+                        };
+                        job.append(
+                            format!(" ({annotation})").as_str(),
+                            0.0,
+                            TextFormat {
+                                style: TextStyle::Monospace,
+                                color: annotation_color,
+                                ..Default::default()
+                            },
                         );
                     }
 
                     ui_inner.label(job);
                 }
                 ui_inner.label(RichText::new("...\n").monospace());
+
+                ui_inner.label(RichText::new("RNG LOG").monospace());
+                ui_inner.label(RichText::new("=======\n").monospace());
+
+                for draw in rng_log
+                    .iter()
+                    .skip(rng_log.len().saturating_sub(LOG_LENGTH))
+                {
+                    let actor_name = actor_name(draw.label.actor, &creatures);
+                    ui_inner.label(RichText::new(draw.describe(&actor_name)).monospace());
+                }
             });
         });
 }
+
+/// A human-readable name for the creature that used an RNG value
+fn actor_name(entity: Entity, creatures: &Query<(Option<&Name>, Option<&Player>)>) -> String {
+    match creatures.get(entity) {
+        Ok((Some(name), _)) => name.as_str().to_string(),
+        Ok((None, Some(_))) => "Player".to_string(),
+        _ => "Monster".to_string(),
+    }
+}
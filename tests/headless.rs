@@ -0,0 +1,131 @@
+//! Runs a scripted fight without a window, as on CI
+
+use bevy::prelude::*;
+use foresight_lib::actions::ActionId;
+use foresight_lib::combat_flow::Active;
+use foresight_lib::combat_setup::{CombatOutcome, CombatResult};
+use foresight_lib::combat_statistics::{Life, Resource};
+use foresight_lib::creatures::{Monster, Player};
+use foresight_lib::headless::{CombatPlugins, HeadlessPlugin, ScriptStep, ScriptedInput};
+use foresight_lib::rng::{RNGOutputs, Rng, RngLog, RollKind};
+
+/// The most frames to run while waiting for something to happen
+const MAX_FRAMES: usize = 100;
+
+/// Starts a fight, and runs it until the player's first turn
+fn setup() -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugins(CombatPlugins)
+        .add_plugin(HeadlessPlugin);
+
+    for _ in 0..MAX_FRAMES {
+        app.update();
+
+        let players_turn = app
+            .world
+            .query_filtered::<(), (With<Player>, With<Active>)>()
+            .iter(&app.world)
+            .next()
+            .is_some();
+        if players_turn {
+            return app;
+        }
+    }
+
+    panic!("It never became the player's turn.");
+}
+
+/// Finds the monster with the provided `name`
+fn monster(app: &mut App, name: &str) -> Entity {
+    app.world
+        .query_filtered::<(Entity, &Name), With<Monster>>()
+        .iter(&app.world)
+        .find(|(_, monster_name)| monster_name.as_str() == name)
+        .map(|(entity, _)| entity)
+        .unwrap_or_else(|| panic!("There is no monster named {name}."))
+}
+
+/// Moves the RNG forward until the next hit roll is high and the dodge roll after it is low
+fn seek_to_certain_hit(app: &mut App) {
+    app.world
+        .resource_scope(|world, mut outputs: Mut<RNGOutputs>| {
+            let mut rng = world.get_resource_mut::<Rng>().unwrap();
+
+            loop {
+                let upcoming: Vec<u8> = outputs.upcoming().map(|value| value.result).collect();
+                if upcoming[0] >= 128 && upcoming[1] < 128 {
+                    return;
+                }
+
+                let index = outputs.index() + 1;
+                outputs.seek(&mut *rng, index);
+            }
+        });
+}
+
+#[test]
+fn scripted_attack_wins_a_one_on_one_fight() {
+    let mut app = setup();
+
+    // Leave a single monster, which any hit defeats
+    let goblin_2 = monster(&mut app, "Goblin 2");
+    app.world.despawn(goblin_2);
+    let goblin = monster(&mut app, "Goblin 1");
+    app.world.entity_mut(goblin).insert(Life::new(1));
+    seek_to_certain_hit(&mut app);
+
+    {
+        let mut script = app.world.get_resource_mut::<ScriptedInput>().unwrap();
+        script.push(ScriptStep::UseOn(ActionId::Attack, "Goblin 1".to_string()));
+        for _ in 0..4 {
+            script.push(ScriptStep::Advance);
+        }
+    }
+
+    let mut reader = app
+        .world
+        .get_resource::<Events<CombatResult>>()
+        .unwrap()
+        .get_reader();
+    let mut results = Vec::new();
+    for _ in 0..MAX_FRAMES {
+        app.update();
+
+        let events = app.world.get_resource::<Events<CombatResult>>().unwrap();
+        results.extend(reader.iter(events).cloned());
+        if !results.is_empty() {
+            break;
+        }
+    }
+
+    let script = app.world.get_resource::<ScriptedInput>().unwrap();
+    assert!(script.rejected().is_empty(), "{:?}", script.rejected());
+
+    // Hit, dodge, crit and damage
+    let rng_log = app.world.get_resource::<RngLog>().unwrap();
+    let rolls: Vec<RollKind> = rng_log
+        .by_action("Attack")
+        .map(|draw| draw.label.roll)
+        .collect();
+    assert_eq!(
+        rolls,
+        [
+            RollKind::Hit,
+            RollKind::Dodge,
+            RollKind::Crit,
+            RollKind::Damage
+        ]
+    );
+
+    assert_eq!(
+        results,
+        [CombatResult {
+            outcome: CombatOutcome::Victory,
+            rounds: 1,
+            turns: 1,
+            defeated: vec!["Goblin 1".to_string()],
+            player_life: 10,
+        }]
+    );
+}
@@ -0,0 +1,372 @@
+//! Checks that each spell uses exactly as many RNG values as the spell catalog in `docs/src/spells_legacy.md` says,
+//! that confused creatures roll as they act, and that actions can change status effects
+
+use bevy::prelude::*;
+use foresight_lib::actions::{ActionData, ActionId, Actions, AvailableActions, Step};
+use foresight_lib::combat_flow::Active;
+use foresight_lib::combat_statistics::{Intelligence, Life, Resource, SpellSuccess};
+use foresight_lib::creatures::{Monster, Player};
+use foresight_lib::headless::{CombatPlugins, HeadlessPlugin, ScriptStep, ScriptedInput};
+use foresight_lib::monster_ai::{MonsterAi, Scripted};
+use foresight_lib::rng::{RNGOutputs, Rng, RngDraw, RngLog, RollKind};
+use foresight_lib::spells::Spellbook;
+use foresight_lib::status_effects::{EffectKind, StatusEffect, StatusEffects};
+
+/// The player's intelligence, which lowers the value needed to cast a spell
+const PLAYER_INTELLIGENCE: Intelligence = Intelligence(5);
+
+/// The most frames to run while waiting for something to happen
+const MAX_FRAMES: usize = 500;
+
+fn parse(ron: &str) -> ActionData {
+    ActionData::from_ron(ron).expect("Spell assets must be valid.")
+}
+
+/// The fail threshold of the spell's first step, which must be a cast
+fn fail_threshold(data: &ActionData) -> u8 {
+    match data.steps.first() {
+        Some(Step::Cast { threshold }) => *threshold,
+        other => panic!("{} should start with a cast, not {other:?}", data.name),
+    }
+}
+
+/// Starts a fight in which the player knows the spell or action described by `data`, returning the player
+fn setup(data: &ActionData) -> (App, Entity) {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugins(CombatPlugins)
+        .add_plugin(HeadlessPlugin);
+
+    app.world
+        .get_resource_mut::<Actions>()
+        .unwrap()
+        .insert(data.to_action());
+
+    let player = wait_for_player_turn(&mut app);
+    if data.is_spell() {
+        app.world
+            .get_mut::<Spellbook>(player)
+            .unwrap()
+            .insert(data.id());
+    } else {
+        app.world
+            .get_mut::<AvailableActions>(player)
+            .unwrap()
+            .insert(data.id());
+    }
+
+    (app, player)
+}
+
+/// Runs frames until it is the player's turn, advancing the monsters' actions along the way
+fn wait_for_player_turn(app: &mut App) -> Entity {
+    for _ in 0..MAX_FRAMES {
+        let player = app
+            .world
+            .query_filtered::<Entity, (With<Player>, With<Active>)>()
+            .iter(&app.world)
+            .next();
+        let idle = app
+            .world
+            .get_resource::<Actions>()
+            .unwrap()
+            .current()
+            .is_none();
+
+        match player {
+            Some(player) if idle => return player,
+            _ => {
+                app.world
+                    .get_resource_mut::<ScriptedInput>()
+                    .unwrap()
+                    .push(ScriptStep::Advance);
+                app.update();
+            }
+        }
+    }
+
+    panic!("It never became the player's turn.");
+}
+
+/// Moves the RNG forward until the "up next" value satisfies the `predicate`
+fn seek_until(app: &mut App, predicate: impl Fn(u8) -> bool) {
+    app.world
+        .resource_scope(|world, mut outputs: Mut<RNGOutputs>| {
+            let mut rng = world.get_resource_mut::<Rng>().unwrap();
+            let start = outputs.index();
+
+            while !predicate(outputs.up_next().result) {
+                let index = outputs.index() + 1;
+                assert!(
+                    index - start < 100_000,
+                    "No suitable RNG value could be found."
+                );
+                outputs.seek(&mut *rng, index);
+            }
+        });
+}
+
+/// Has the `player` cast the spell or use the action described by `data`, returning the number of RNG values that they used for it
+fn cast(app: &mut App, player: Entity, data: &ActionData) -> usize {
+    app.world
+        .get_resource_mut::<ScriptedInput>()
+        .unwrap()
+        .use_action(data.id(), 30);
+
+    for _ in 0..MAX_FRAMES {
+        if app
+            .world
+            .get_resource::<ScriptedInput>()
+            .unwrap()
+            .is_finished()
+        {
+            break;
+        }
+        app.update();
+    }
+
+    let script = app.world.get_resource::<ScriptedInput>().unwrap();
+    assert!(
+        script.rejected().is_empty(),
+        "Casting {} was refused: {:?}",
+        data.name,
+        script.rejected()
+    );
+
+    let rng_log = app.world.get_resource::<RngLog>().unwrap();
+    // Status effects can use RNG values labelled with the same name on the target's turn
+    rng_log
+        .by_action(&data.name)
+        .filter(|draw| draw.label.actor == player)
+        .count()
+}
+
+/// Checks that the spell described by the `ron` uses `draws` RNG values when cast successfully, and one when it fizzles
+fn check_draws(ron: &str, draws: usize) {
+    let data = parse(ron);
+    assert!(data.is_spell(), "{} should be a spell", data.name);
+    assert_eq!(
+        data.to_action().max_draws(),
+        Some(draws),
+        "{} should use at most {draws} RNG values",
+        data.name
+    );
+
+    let needed = SpellSuccess::new(fail_threshold(&data), PLAYER_INTELLIGENCE).threshold();
+
+    let (mut app, player) = setup(&data);
+    seek_until(&mut app, |value| value >= needed);
+    assert_eq!(
+        cast(&mut app, player, &data),
+        draws,
+        "{} should use {draws} RNG values when cast",
+        data.name
+    );
+
+    // Spells that the player cannot fail to cast never fizzle
+    if needed > 0 {
+        let (mut app, player) = setup(&data);
+        seek_until(&mut app, |value| value < needed);
+        assert_eq!(
+            cast(&mut app, player, &data),
+            1,
+            "{} should stop after its cast roll when it fizzles",
+            data.name
+        );
+    }
+}
+
+#[test]
+fn polymorph_draws() {
+    check_draws(include_str!("../assets/actions/polymorph.action.ron"), 2);
+}
+
+#[test]
+fn power_word_kill_draws() {
+    check_draws(
+        include_str!("../assets/actions/power_word_kill.action.ron"),
+        1,
+    );
+}
+
+#[test]
+fn confusion_draws() {
+    check_draws(include_str!("../assets/actions/confusion.action.ron"), 1);
+}
+
+#[test]
+fn lightning_bolt_draws() {
+    check_draws(
+        include_str!("../assets/actions/lightning_bolt.action.ron"),
+        3,
+    );
+}
+
+#[test]
+fn mana_leak_draws() {
+    check_draws(include_str!("../assets/actions/mana_leak.action.ron"), 2);
+}
+
+#[test]
+fn evasion_draws() {
+    check_draws(include_str!("../assets/actions/evasion.action.ron"), 1);
+}
+
+#[test]
+fn lucky_strike_draws() {
+    check_draws(include_str!("../assets/actions/lucky_strike.action.ron"), 1);
+}
+
+#[test]
+fn bees_draws() {
+    check_draws(include_str!("../assets/actions/bees.action.ron"), 21);
+}
+
+#[test]
+fn fire_bolt_draws() {
+    check_draws(include_str!("../assets/actions/fire_bolt.action.ron"), 3);
+}
+
+#[test]
+fn super_scan_draws() {
+    check_draws(include_str!("../assets/actions/super_scan.action.ron"), 1);
+}
+
+#[test]
+fn hemokinesis_draws() {
+    check_draws(include_str!("../assets/actions/hemokinesis.action.ron"), 1);
+}
+
+#[test]
+fn blood_letting_draws() {
+    check_draws(
+        include_str!("../assets/actions/blood_letting.action.ron"),
+        1,
+    );
+}
+
+/// Confuses the only monster, then starts its turn with a value satisfying the `confusion_roll` up next
+///
+/// Returns the monster, the player and the draws made on the monster's turn.
+fn confused_monster_turn(confusion_roll: impl Fn(u8) -> bool) -> (Entity, Entity, Vec<RngDraw>) {
+    let data = parse(include_str!("../assets/actions/confusion.action.ron"));
+    let (mut app, player) = setup(&data);
+
+    let mut monsters = app.world.query_filtered::<(Entity, &Name), With<Monster>>();
+    let monsters: Vec<(Entity, String)> = monsters
+        .iter(&app.world)
+        .map(|(entity, name)| (entity, name.as_str().to_string()))
+        .collect();
+    let goblin = monsters
+        .iter()
+        .find(|(_, name)| name == "Goblin 1")
+        .map(|(entity, _)| *entity)
+        .expect("There should be a monster named Goblin 1.");
+    for (monster, _) in &monsters {
+        if *monster != goblin {
+            app.world.despawn(*monster);
+        }
+    }
+
+    // The goblin attacks without deciding, so that its first draw is its confusion roll,
+    // and neither side can be defeated by its attacks
+    app.world
+        .entity_mut(goblin)
+        .insert(MonsterAi::new(Scripted::new([ActionId::Attack])))
+        .insert(Life::new(u8::MAX));
+    app.world.entity_mut(player).insert(Life::new(u8::MAX));
+    app.world
+        .get_mut::<StatusEffects>(goblin)
+        .unwrap()
+        .apply(StatusEffect::new(EffectKind::Confusion(51), 5));
+
+    seek_until(&mut app, confusion_roll);
+    let start = app.world.get_resource::<RNGOutputs>().unwrap().index();
+
+    {
+        let mut script = app.world.get_resource_mut::<ScriptedInput>().unwrap();
+        script.push(ScriptStep::EndTurn);
+        for _ in 0..10 {
+            script.push(ScriptStep::Advance);
+        }
+    }
+    for _ in 0..MAX_FRAMES {
+        if app
+            .world
+            .get_resource::<ScriptedInput>()
+            .unwrap()
+            .is_finished()
+        {
+            break;
+        }
+        app.update();
+    }
+
+    let draws = app
+        .world
+        .get_resource::<RngLog>()
+        .unwrap()
+        .since(start)
+        .cloned()
+        .collect();
+    (goblin, player, draws)
+}
+
+#[test]
+fn confused_monsters_can_attack_themselves() {
+    let (goblin, _, draws) = confused_monster_turn(|value| value < 51);
+
+    assert_eq!(draws[0].label.action, "Confusion");
+    assert_eq!(draws[0].label.actor, goblin);
+    assert_eq!(draws[0].label.roll, RollKind::Status);
+    assert!(draws[0].value < 51);
+
+    // The goblin rolls to hit, then dodges its own attack
+    assert_eq!(draws[1].label.roll, RollKind::Hit);
+    assert_eq!(draws[2].label.roll, RollKind::Dodge);
+    assert_eq!(draws[2].label.actor, goblin);
+}
+
+#[test]
+fn confused_monsters_can_keep_their_focus() {
+    let (goblin, player, draws) = confused_monster_turn(|value| value >= 51);
+
+    assert_eq!(draws[0].label.action, "Confusion");
+    assert_eq!(draws[0].label.actor, goblin);
+    assert_eq!(draws[0].label.roll, RollKind::Status);
+    assert!(draws[0].value >= 51);
+
+    // The player dodges the goblin's attack as usual
+    assert_eq!(draws[1].label.roll, RollKind::Hit);
+    assert_eq!(draws[2].label.roll, RollKind::Dodge);
+    assert_eq!(draws[2].label.actor, player);
+}
+
+#[test]
+fn reversal_turns_debuffs_into_regeneration() {
+    let data = parse(include_str!("../assets/actions/reversal.action.ron"));
+    let (mut app, player) = setup(&data);
+
+    let mut effects = StatusEffects::default();
+    effects.apply(StatusEffect::new(EffectKind::Bleeding(2), 3));
+    effects.apply(StatusEffect::new(EffectKind::Confusion(51), 2));
+    app.world.entity_mut(player).insert(effects);
+
+    assert_eq!(cast(&mut app, player, &data), 0);
+
+    // Confusion has no inverse, so it stays as it was
+    let effects: Vec<StatusEffect> = app
+        .world
+        .get::<StatusEffects>(player)
+        .unwrap()
+        .iter()
+        .copied()
+        .collect();
+    assert_eq!(
+        effects,
+        [
+            StatusEffect::new(EffectKind::Confusion(51), 2),
+            StatusEffect::new(EffectKind::Regeneration(2), 3)
+        ]
+    );
+}